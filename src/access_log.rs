@@ -0,0 +1,477 @@
+//! The access logging module.
+//!
+//! It provides a configurable [`AccessLog`] component made of a before and after middleware pair
+//! intended to be plugged in via [`Middlewares::link`][`super::Middlewares::link`].
+//!
+//! - The [`AccessLogBefore`] middleware records the time a request started.
+//! - The [`AccessLogAfter`] middleware writes an entry per request to a [`LogSink`] including
+//!   the method, URI, status, response size, duration, remote address and user agent.
+//!   Requests failing through the error flow are logged as well with the status of the error
+//!   (or `500` if it has none).
+//!
+//! The entries can be written in [Common Log Format](https://httpd.apache.org/docs/current/logs.html#common),
+//! [Combined Log Format](https://httpd.apache.org/docs/current/logs.html#combined) or as JSON lines. See [`LogFormat`].
+//!
+//! The remote address is taken from the [`SocketAddr`] inserted into the request extensions by the [`Service`][`super::Service`].
+//! The response size is taken from the `Content-Length` header or the body size if known. Responses with a streamed body of unknown length
+//! are logged once their body was sent so the actual number of bytes is recorded.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{AccessLog, Body, LogFormat, Middlewares, Request, Response, Result};
+//!
+//! let handler = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::from("¡Hola!"))) };
+//!
+//! let access_log = AccessLog::new()
+//!     .format(LogFormat::Json)
+//!     .sink(|line: &str| eprintln!("{}", line));
+//!
+//! let mut middlewares = Middlewares::new(handler);
+//! middlewares.link(access_log.build());
+//! ```
+
+use async_trait::async_trait;
+use futures_core::Stream;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{self, HeaderMap};
+use hyper::{Method, StatusCode, Uri, Version};
+use std::fmt::{self, Write as _};
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::middleware::{AfterMiddleware, BeforeMiddleware};
+use crate::router::OriginalUri;
+use crate::{Body, Error, Request, Response, Result};
+
+/// The format of the access log entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The Common Log Format, e.g.
+    /// `127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326`.
+    Common,
+    /// The Combined Log Format which extends the Common Log Format with the `Referer` and `User-Agent` headers.
+    Combined,
+    /// One JSON object per line including the request duration.
+    Json,
+}
+
+/// Defines where the access log entries are written to.
+///
+/// It's implemented for closures taking the formatted line, so custom sinks (e.g. a file or a logging crate)
+/// can be plugged in easily.
+pub trait LogSink: Send + Sync + 'static {
+    /// Write a formatted access log entry, without a trailing newline.
+    fn write(&self, line: &str);
+}
+
+impl<F> LogSink for F
+where
+    F: Fn(&str) + Send + Sync + 'static,
+{
+    fn write(&self, line: &str) {
+        (*self)(line)
+    }
+}
+
+/// A [`LogSink`] which writes the entries to the standard output.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutSink;
+
+impl LogSink for StdoutSink {
+    fn write(&self, line: &str) {
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = writeln!(stdout, "{}", line);
+    }
+}
+
+/// The access log configuration which produces an [`AccessLogBefore`] and [`AccessLogAfter`] middleware pair.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Clone)]
+pub struct AccessLog {
+    format: LogFormat,
+    sink: Arc<dyn LogSink>,
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl Default for AccessLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccessLog {
+    /// Create a new access log configuration which writes Common Log Format entries to the standard output.
+    pub fn new() -> Self {
+        Self {
+            format: LogFormat::Common,
+            sink: Arc::new(StdoutSink),
+        }
+    }
+
+    /// Set the format of the entries.
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the sink the entries are written to.
+    pub fn sink<S>(mut self, sink: S) -> Self
+    where
+        S: LogSink,
+    {
+        self.sink = Arc::new(sink);
+        self
+    }
+
+    /// Build the middleware pair to be plugged in via [`Middlewares::link`][`super::Middlewares::link`].
+    pub fn build(self) -> (AccessLogBefore, AccessLogAfter) {
+        let log = Arc::new(self);
+        (AccessLogBefore {}, AccessLogAfter { log })
+    }
+}
+
+/// The time a request started, stored in the request extensions by [`AccessLogBefore`].
+#[derive(Debug, Clone, Copy)]
+struct RequestStart {
+    instant: Instant,
+    time: SystemTime,
+}
+
+impl RequestStart {
+    fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            time: SystemTime::now(),
+        }
+    }
+}
+
+/// The [`BeforeMiddleware`] part of [`AccessLog`] which records the time a request started.
+#[derive(Debug, Clone)]
+pub struct AccessLogBefore {}
+
+#[async_trait]
+impl BeforeMiddleware for AccessLogBefore {
+    async fn before(&self, req: &mut Request) -> Result {
+        req.extensions_mut().insert(RequestStart::now());
+        Ok(())
+    }
+
+    async fn catch(&self, req: &mut Request, err: Error) -> Result {
+        req.extensions_mut().insert(RequestStart::now());
+        Err(err)
+    }
+}
+
+/// The [`AfterMiddleware`] part of [`AccessLog`] which writes the entries.
+#[derive(Debug, Clone)]
+pub struct AccessLogAfter {
+    log: Arc<AccessLog>,
+}
+
+#[async_trait]
+impl AfterMiddleware for AccessLogAfter {
+    async fn after(&self, req: &mut Request, res: Response) -> Result<Response> {
+        let length = res
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .or_else(|| HttpBody::size_hint(res.body()).exact());
+        let entry = Entry::new(req, res.status());
+
+        if length.is_some() {
+            self.log
+                .write(&entry, length, entry.start.instant.elapsed());
+            return Ok(res);
+        }
+
+        // Log once the streamed body was sent
+        let (parts, body) = res.into_parts();
+        let body = Body::wrap_stream(CountingBody {
+            body,
+            bytes: 0,
+            pending: Some((self.log.clone(), entry)),
+        });
+        Ok(Response::from_parts(parts, body))
+    }
+
+    async fn catch(&self, req: &mut Request, err: Error) -> Result<Response> {
+        let status = err.status().unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let entry = Entry::new(req, status);
+        self.log.write(&entry, None, entry.start.instant.elapsed());
+        Err(err)
+    }
+}
+
+// The request data of an access log entry.
+struct Entry {
+    start: RequestStart,
+    method: Method,
+    uri: Uri,
+    version: Version,
+    status: StatusCode,
+    remote_addr: Option<SocketAddr>,
+    user_agent: Option<String>,
+    referer: Option<String>,
+}
+
+impl Entry {
+    fn new(req: &Request, status: StatusCode) -> Self {
+        let uri = req
+            .extensions()
+            .get::<OriginalUri>()
+            .map(|original| original.0.clone())
+            .unwrap_or_else(|| req.uri().clone());
+        Self {
+            start: req
+                .extensions()
+                .get::<RequestStart>()
+                .copied()
+                .unwrap_or_else(RequestStart::now),
+            method: req.method().clone(),
+            uri,
+            version: req.version(),
+            status,
+            remote_addr: req.extensions().get::<SocketAddr>().copied(),
+            user_agent: header_string(req.headers(), header::USER_AGENT),
+            referer: header_string(req.headers(), header::REFERER),
+        }
+    }
+}
+
+impl AccessLog {
+    fn write(&self, entry: &Entry, bytes: Option<u64>, duration: Duration) {
+        let line = match self.format {
+            LogFormat::Common => common_line(entry, bytes),
+            LogFormat::Combined => {
+                let mut line = common_line(entry, bytes);
+                let _ = write!(
+                    line,
+                    " \"{}\" \"{}\"",
+                    escape_quoted(entry.referer.as_deref().unwrap_or("-")),
+                    escape_quoted(entry.user_agent.as_deref().unwrap_or("-"))
+                );
+                line
+            }
+            LogFormat::Json => json_line(entry, bytes, duration),
+        };
+        self.sink.write(&line);
+    }
+}
+
+fn common_line(entry: &Entry, bytes: Option<u64>) -> String {
+    let remote_addr = entry
+        .remote_addr
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "-".to_owned());
+    let bytes = bytes
+        .map(|b| b.to_string())
+        .unwrap_or_else(|| "-".to_owned());
+    format!(
+        "{} - - [{}] \"{} {} {:?}\" {} {}",
+        remote_addr,
+        clf_time(entry.start.time),
+        entry.method,
+        escape_quoted(&entry.uri.to_string()),
+        entry.version,
+        entry.status.as_u16(),
+        bytes
+    )
+}
+
+fn json_line(entry: &Entry, bytes: Option<u64>, duration: Duration) -> String {
+    let mut line = String::from("{");
+    let _ = write!(
+        line,
+        "\"time\":{}",
+        json_string(&rfc3339_time(entry.start.time))
+    );
+    let _ = write!(
+        line,
+        ",\"remote_addr\":{}",
+        json_opt(entry.remote_addr.map(|a| a.to_string()).as_deref())
+    );
+    let _ = write!(line, ",\"method\":{}", json_string(entry.method.as_str()));
+    let _ = write!(line, ",\"uri\":{}", json_string(&entry.uri.to_string()));
+    let _ = write!(
+        line,
+        ",\"version\":{}",
+        json_string(&format!("{:?}", entry.version))
+    );
+    let _ = write!(line, ",\"status\":{}", entry.status.as_u16());
+    match bytes {
+        Some(bytes) => {
+            let _ = write!(line, ",\"bytes\":{}", bytes);
+        }
+        None => line.push_str(",\"bytes\":null"),
+    }
+    let _ = write!(
+        line,
+        ",\"duration_ms\":{:.3}",
+        duration.as_secs_f64() * 1000.0
+    );
+    let _ = write!(
+        line,
+        ",\"user_agent\":{}",
+        json_opt(entry.user_agent.as_deref())
+    );
+    let _ = write!(line, ",\"referer\":{}", json_opt(entry.referer.as_deref()));
+    line.push('}');
+    line
+}
+
+/// A body stream which counts the bytes sent and writes the access log entry once done.
+struct CountingBody {
+    body: Body,
+    bytes: u64,
+    pending: Option<(Arc<AccessLog>, Entry)>,
+}
+
+impl CountingBody {
+    fn finish(&mut self) {
+        if let Some((log, entry)) = self.pending.take() {
+            log.write(&entry, Some(self.bytes), entry.start.instant.elapsed());
+        }
+    }
+}
+
+impl Stream for CountingBody {
+    type Item = std::result::Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match Pin::new(&mut self.body).poll_data(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                self.bytes += chunk.len() as u64;
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(None) => {
+                self.finish();
+                Poll::Ready(None)
+            }
+            other => other,
+        }
+    }
+}
+
+impl Drop for CountingBody {
+    fn drop(&mut self) {
+        // The body was aborted (e.g. the client disconnected) or failed
+        self.finish();
+    }
+}
+
+fn header_string(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+}
+
+fn escape_quoted(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\x{:02x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_opt(value: Option<&str>) -> String {
+    value.map(json_string).unwrap_or_else(|| "null".to_owned())
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Split a time into its UTC date and time components.
+fn utc_parts(time: SystemTime) -> (i64, u32, u32, u64, u64, u64, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+// Format a time as `10/Oct/2000:13:55:36 +0000`.
+fn clf_time(time: SystemTime) -> String {
+    let (year, month, day, hour, min, sec, _) = utc_parts(time);
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        hour,
+        min,
+        sec
+    )
+}
+
+// Format a time as `2000-10-10T13:55:36.000Z`.
+fn rfc3339_time(time: SystemTime) -> String {
+    let (year, month, day, hour, min, sec, millis) = utc_parts(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day, hour, min, sec, millis
+    )
+}
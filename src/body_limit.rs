@@ -0,0 +1,146 @@
+//! The request body size limiting module.
+//!
+//! It provides a [`BodyLimit`] middleware which protects handlers from unbounded request bodies.
+//!
+//! - Requests declaring a `Content-Length` greater than the limit are rejected up front
+//!   with a `413 Payload Too Large` error.
+//! - Requests with a body but no `Content-Length` (e.g. chunked uploads) get their body wrapped so
+//!   reading it fails with a `413 Payload Too Large` error as soon as the limit is exceeded.
+//!   Optionally, such requests can be rejected with a `411 Length Required` error instead.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{async_trait, Body, BodyLimit, Handler, Middlewares, Request, Response, Result};
+//!
+//! struct Application {}
+//!
+//! #[async_trait]
+//! impl Handler for Application {
+//!     async fn handle(&self, req: &mut Request) -> Result<Response> {
+//!         // Fails with `413 Payload Too Large` if the body exceeds the limit
+//!         let body = hyper::body::to_bytes(std::mem::take(req.body_mut())).await?;
+//!         Ok(Response::new(Body::from(format!("{} bytes received", body.len()))))
+//!     }
+//! }
+//!
+//! let mut middlewares = Middlewares::new(Application {});
+//! // Limit request bodies to 1 MiB
+//! middlewares.link_before(BodyLimit::new(1024 * 1024));
+//! ```
+
+use async_trait::async_trait;
+use futures_core::Stream;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+use hyper::StatusCode;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::middleware::BeforeMiddleware;
+use crate::{
+    http_error_bad_request, http_error_length_required, http_error_payload_too_large, Body, Error,
+    Request, Result,
+};
+
+/// A [`BeforeMiddleware`] which enforces a maximum request body size.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct BodyLimit {
+    limit: u64,
+    require_length: bool,
+}
+
+impl BodyLimit {
+    /// Create a new middleware which limits request bodies to the given number of bytes.
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            require_length: false,
+        }
+    }
+
+    /// Reject requests with a body but no `Content-Length` header with a `411 Length Required` error.
+    ///
+    /// Disabled by default.
+    pub fn require_length(mut self, require: bool) -> Self {
+        self.require_length = require;
+        self
+    }
+}
+
+#[async_trait]
+impl BeforeMiddleware for BodyLimit {
+    async fn before(&self, req: &mut Request) -> Result {
+        if let Some(value) = req.headers().get(CONTENT_LENGTH) {
+            let length = value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .ok_or_else(|| http_error_bad_request!("invalid content-length header"))?;
+            if length > self.limit {
+                return Err(payload_too_large(self.limit));
+            }
+            // Hyper itself makes sure that the body does not exceed its declared length
+            return Ok(());
+        }
+
+        let has_body = req.headers().contains_key(TRANSFER_ENCODING) || !req.body().is_end_stream();
+        if !has_body {
+            return Ok(());
+        }
+        if self.require_length {
+            return Err(http_error_length_required!(
+                "a content-length header is required"
+            ));
+        }
+
+        let body = std::mem::take(req.body_mut());
+        *req.body_mut() = Body::wrap_stream(LimitedBody::new(body, self.limit));
+
+        Ok(())
+    }
+}
+
+fn payload_too_large(limit: u64) -> Error {
+    http_error_payload_too_large!("request body exceeds the limit of {} bytes", limit)
+}
+
+/// A body stream which fails once more than `limit` bytes were read.
+pub(crate) struct LimitedBody {
+    body: Body,
+    remaining: u64,
+    limit: u64,
+}
+
+impl LimitedBody {
+    pub(crate) fn new(body: Body, limit: u64) -> Self {
+        Self {
+            body,
+            remaining: limit,
+            limit,
+        }
+    }
+}
+
+impl Stream for LimitedBody {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match Pin::new(&mut self.body).poll_data(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                let len = chunk.len() as u64;
+                if len > self.remaining {
+                    self.remaining = 0;
+                    return Poll::Ready(Some(Err(payload_too_large(self.limit))));
+                }
+                self.remaining -= len;
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! The client certificates module (`rustls` feature).
+//!
+//! When mutual TLS is enabled via [`TlsConfig::client_auth`][`super::TlsConfig::client_auth`],
+//! the verified client certificate chain is inserted into the request extensions as [`PeerCertificates`].
+//!
+//! It also provides a [`ClientCertAuth`] middleware which authorizes requests by client certificate
+//! subject, subject alternative name (SAN) or SHA-256 fingerprint allow-lists:
+//!
+//! - Requests without a client certificate are rejected with `401 Unauthorized`.
+//! - Requests whose certificate matches none of the allow-lists are rejected with `403 Forbidden`.
+//!
+//! If no allow-list is configured, any verified client certificate is accepted.
+//!
+//! ## Subjects
+//!
+//! Subjects are compared by their attributes (type and value) in the order of the certificate encoding,
+//! which is the order [`PeerCertificate::subject`] displays them in. It's usually the most significant
+//! attribute first (e.g. `C=ES, O=Acme, CN=service-a`), that is the reverse of RFC 4514 strings
+//! like the ones printed by `openssl x509 -nameopt RFC2253`.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{
+//!     Body, ClientCertAuth, Middlewares, PeerCertificates, Request, Response, Result,
+//! };
+//!
+//! let handler = |req: &mut Request| -> Result<Response> {
+//!     let certs = req.extensions().get::<PeerCertificates>().unwrap();
+//!     Ok(Response::new(Body::from(format!("¡Hola, {}!", certs.leaf().subject()))))
+//! };
+//!
+//! let auth = ClientCertAuth::new()
+//!     .allow_subject("O=Acme, CN=service-a")
+//!     .allow_dns("service-b.internal")
+//!     .allow_ip([10, 0, 0, 7].into())
+//!     .allow_fingerprint("3f:1c:5e:...");
+//!
+//! let mut middlewares = Middlewares::new(handler);
+//! middlewares.link_before(auth);
+//! ```
+
+use async_trait::async_trait;
+use hyper::StatusCode;
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate, X509Name};
+
+use crate::middleware::BeforeMiddleware;
+use crate::{http_error_forbidden, http_error_unauthorized, Request, Result};
+
+/// A subject alternative name (SAN) of a client certificate.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SubjectAltName {
+    /// A DNS name (e.g. `service-a.internal`).
+    Dns(String),
+    /// An IP address.
+    Ip(IpAddr),
+    /// An email address.
+    Email(String),
+    /// A URI (e.g. a SPIFFE ID like `spiffe://example.org/service-a`).
+    Uri(String),
+}
+
+impl SubjectAltName {
+    // DNS names and email domains are case-insensitive.
+    fn normalize(self) -> Self {
+        match self {
+            Self::Dns(name) => Self::Dns(name.trim().trim_end_matches('.').to_ascii_lowercase()),
+            Self::Email(email) => Self::Email(match email.trim().rsplit_once('@') {
+                Some((local, domain)) => format!("{}@{}", local, domain.to_ascii_lowercase()),
+                None => email.trim().to_owned(),
+            }),
+            Self::Uri(uri) => Self::Uri(uri.trim().to_owned()),
+            ip => ip,
+        }
+    }
+}
+
+impl fmt::Display for SubjectAltName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns(name) => write!(f, "DNS:{}", name),
+            Self::Ip(ip) => write!(f, "IP:{}", ip),
+            Self::Email(email) => write!(f, "email:{}", email),
+            Self::Uri(uri) => write!(f, "URI:{}", uri),
+        }
+    }
+}
+
+/// A client certificate of a [`PeerCertificates`] chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificate {
+    der: CertificateDer<'static>,
+    subject: String,
+    subject_attributes: Vec<Attribute>,
+    issuer: String,
+    sans: Vec<SubjectAltName>,
+    fingerprint: String,
+}
+
+impl PeerCertificate {
+    fn from_der(der: &CertificateDer<'static>) -> Self {
+        let digest = ring::digest::digest(&ring::digest::SHA256, der.as_ref());
+        let fingerprint = digest
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        let mut subject = String::new();
+        let mut subject_attributes = vec![];
+        let mut issuer = String::new();
+        let mut sans = vec![];
+        // The chain was already verified, so parsing should not fail
+        if let Ok((_, cert)) = X509Certificate::from_der(der.as_ref()) {
+            subject = cert.subject().to_string();
+            subject_attributes = name_attributes(cert.subject());
+            issuer = cert.issuer().to_string();
+            if let Ok(Some(ext)) = cert.subject_alternative_name() {
+                sans = ext
+                    .value
+                    .general_names
+                    .iter()
+                    .filter_map(general_name)
+                    .collect();
+            }
+        }
+
+        Self {
+            der: der.clone(),
+            subject,
+            subject_attributes,
+            issuer,
+            sans,
+            fingerprint,
+        }
+    }
+
+    /// Returns the DER encoded certificate.
+    pub fn der(&self) -> &[u8] {
+        self.der.as_ref()
+    }
+
+    /// Returns the subject distinguished name (e.g. `O=Acme, CN=service-a`).
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Returns the issuer distinguished name.
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Returns the DNS names, IP addresses, emails and URIs of the subject alternative names.
+    pub fn sans(&self) -> &[SubjectAltName] {
+        &self.sans
+    }
+
+    /// Returns the SHA-256 fingerprint as lowercase hexadecimal without separators.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+}
+
+/// The verified client certificate chain of a connection, leaf certificate first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificates(Arc<Vec<PeerCertificate>>);
+
+impl PeerCertificates {
+    // The chain must not be empty.
+    pub(crate) fn from_chain(chain: &[CertificateDer<'static>]) -> Self {
+        Self(Arc::new(
+            chain.iter().map(PeerCertificate::from_der).collect(),
+        ))
+    }
+
+    /// Returns the leaf (client) certificate.
+    pub fn leaf(&self) -> &PeerCertificate {
+        &self.0[0]
+    }
+
+    /// Returns the whole certificate chain.
+    pub fn chain(&self) -> &[PeerCertificate] {
+        &self.0
+    }
+}
+
+/// A [`BeforeMiddleware`] which authorizes requests by client certificate.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone, Default)]
+pub struct ClientCertAuth {
+    subjects: HashSet<Vec<Attribute>>,
+    sans: HashSet<SubjectAltName>,
+    fingerprints: HashSet<String>,
+}
+
+impl ClientCertAuth {
+    /// Create a new middleware which accepts any verified client certificate.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow a client certificate subject distinguished name (e.g. `O=Acme, CN=service-a`).
+    ///
+    /// Attributes are written as `TYPE=value` pairs separated by `,` (or `+` within a multi-valued RDN)
+    /// and must be in the order of the certificate encoding, see the [module documentation][`self`].
+    /// Types are either short names (`CN`, `O`, `OU`, `C`, `L`, `ST`, `DC`, `emailAddress`, etc.)
+    /// or dotted OIDs, and values may contain `\` escaped characters as in RFC 4514.
+    /// Values are compared case-insensitively and unknown types never match.
+    pub fn allow_subject(mut self, subject: &str) -> Self {
+        self.subjects.insert(parse_dn(subject));
+        self
+    }
+
+    /// Allow a client certificate DNS subject alternative name, compared case-insensitively.
+    pub fn allow_dns(mut self, name: &str) -> Self {
+        self.sans
+            .insert(SubjectAltName::Dns(name.to_owned()).normalize());
+        self
+    }
+
+    /// Allow a client certificate IP address subject alternative name.
+    pub fn allow_ip(mut self, ip: IpAddr) -> Self {
+        self.sans.insert(SubjectAltName::Ip(ip));
+        self
+    }
+
+    /// Allow a client certificate email subject alternative name,
+    /// whose domain is compared case-insensitively.
+    pub fn allow_email(mut self, email: &str) -> Self {
+        self.sans
+            .insert(SubjectAltName::Email(email.to_owned()).normalize());
+        self
+    }
+
+    /// Allow a client certificate URI subject alternative name (e.g. a SPIFFE ID).
+    pub fn allow_uri(mut self, uri: &str) -> Self {
+        self.sans
+            .insert(SubjectAltName::Uri(uri.to_owned()).normalize());
+        self
+    }
+
+    /// Allow a client certificate SHA-256 fingerprint in hexadecimal,
+    /// with or without `:` separators.
+    pub fn allow_fingerprint(mut self, fingerprint: &str) -> Self {
+        self.fingerprints.insert(normalize_fingerprint(fingerprint));
+        self
+    }
+
+    /// Returns `true` if the given client certificate is allowed.
+    pub fn is_allowed(&self, cert: &PeerCertificate) -> bool {
+        if self.subjects.is_empty() && self.sans.is_empty() && self.fingerprints.is_empty() {
+            return true;
+        }
+        self.subjects.contains(&cert.subject_attributes)
+            || self.fingerprints.contains(cert.fingerprint())
+            || cert
+                .sans()
+                .iter()
+                .any(|san| self.sans.contains(&san.clone().normalize()))
+    }
+}
+
+#[async_trait]
+impl BeforeMiddleware for ClientCertAuth {
+    async fn before(&self, req: &mut Request) -> Result {
+        let certs = match req.extensions().get::<PeerCertificates>() {
+            Some(certs) => certs,
+            None => return Err(http_error_unauthorized!("client certificate required")),
+        };
+        let cert = certs.leaf();
+        if !self.is_allowed(cert) {
+            return Err(http_error_forbidden!(
+                "client certificate `{}` is not allowed",
+                cert.subject()
+            ));
+        }
+        Ok(())
+    }
+}
+
+// A distinguished name attribute: the dotted OID of its type (or the unknown type name) and its lowercase value.
+type Attribute = (String, String);
+
+// The short names of the common attribute types.
+const ATTRIBUTE_TYPES: &[(&str, &str)] = &[
+    ("CN", "2.5.4.3"),
+    ("SN", "2.5.4.4"),
+    ("SERIALNUMBER", "2.5.4.5"),
+    ("C", "2.5.4.6"),
+    ("L", "2.5.4.7"),
+    ("ST", "2.5.4.8"),
+    ("STREET", "2.5.4.9"),
+    ("O", "2.5.4.10"),
+    ("OU", "2.5.4.11"),
+    ("TITLE", "2.5.4.12"),
+    ("GN", "2.5.4.42"),
+    ("UID", "0.9.2342.19200300.100.1.1"),
+    ("DC", "0.9.2342.19200300.100.1.25"),
+    ("EMAIL", "1.2.840.113549.1.9.1"),
+    ("EMAILADDRESS", "1.2.840.113549.1.9.1"),
+];
+
+fn name_attributes(name: &X509Name<'_>) -> Vec<Attribute> {
+    name.iter_attributes()
+        .map(|attr| {
+            let value = match attr.as_str() {
+                Ok(value) => value.to_lowercase(),
+                // Non-string values are written as `#` and the hexadecimal value like in RFC 4514
+                Err(_) => std::iter::once("#".to_owned())
+                    .chain(attr.as_slice().iter().map(|b| format!("{:02x}", b)))
+                    .collect(),
+            };
+            (attr.attr_type().to_id_string(), value)
+        })
+        .collect()
+}
+
+fn parse_dn(dn: &str) -> Vec<Attribute> {
+    split_unescaped(dn, &[',', '+'])
+        .into_iter()
+        .map(|attr| {
+            let (key, value) = attr.split_once('=').unwrap_or((&attr, ""));
+            let key = key.trim();
+            let oid = if key.chars().all(|c| c.is_ascii_digit() || c == '.') {
+                key.to_owned()
+            } else {
+                let key = key.to_ascii_uppercase();
+                ATTRIBUTE_TYPES
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, oid)| (*oid).to_owned())
+                    .unwrap_or(key)
+            };
+            (oid, unescape(value.trim()).to_lowercase())
+        })
+        .collect()
+}
+
+// Split a string on the given separators unless they are escaped, keeping the escapes.
+fn split_unescaped(s: &str, separators: &[char]) -> Vec<String> {
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                part.push(c);
+                if let Some(next) = chars.next() {
+                    part.push(next);
+                }
+            }
+            c if separators.contains(&c) => parts.push(std::mem::take(&mut part)),
+            c => part.push(c),
+        }
+    }
+    parts.push(part);
+    parts
+}
+
+// Unescape `\` followed by a special character or a hexadecimal byte pair.
+fn unescape(value: &str) -> String {
+    let mut bytes = vec![];
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let hex = chars.clone().take(2).collect::<String>();
+        match u8::from_str_radix(&hex, 16) {
+            // `from_str_radix` accepts a sign, so both characters must be digits
+            Ok(b) if hex.len() == 2 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                bytes.push(b);
+                chars.next();
+                chars.next();
+            }
+            _ => {
+                if let Some(next) = chars.next() {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(next.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn general_name(name: &GeneralName<'_>) -> Option<SubjectAltName> {
+    match name {
+        GeneralName::DNSName(name) => Some(SubjectAltName::Dns((*name).to_owned())),
+        GeneralName::RFC822Name(email) => Some(SubjectAltName::Email((*email).to_owned())),
+        GeneralName::URI(uri) => Some(SubjectAltName::Uri((*uri).to_owned())),
+        GeneralName::IPAddress(bytes) => match bytes.len() {
+            4 => <[u8; 4]>::try_from(*bytes).ok().map(IpAddr::from),
+            16 => <[u8; 16]>::try_from(*bytes).ok().map(IpAddr::from),
+            _ => None,
+        }
+        .map(SubjectAltName::Ip),
+        _ => None,
+    }
+}
+
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A self-signed certificate whose subject is `C=ES, O=Acme\, Inc, OU=Ops+UID=svc-a, CN=Service-A`
+    // with the `DNS:Svc.Internal`, `IP:10.0.0.7`, `email:ops@Example.COM` and `URI:spiffe://example.org/a` SANs.
+    const CLIENT_PEM: &[u8] = include_bytes!("../tests/certs/client.pem");
+
+    const CLIENT_FINGERPRINT: &str =
+        "00:11:FD:16:DE:99:62:F9:77:F0:7D:C5:CE:12:2A:E0:6A:76:27:61:D9:EF:A7:1A:99:C2:90:E2:0A:D5:51:B2";
+
+    fn client() -> PeerCertificates {
+        let cert = rustls_pemfile::certs(&mut &CLIENT_PEM[..])
+            .next()
+            .unwrap()
+            .unwrap();
+        PeerCertificates::from_chain(&[cert])
+    }
+
+    fn attributes(attrs: &[(&str, &str)]) -> Vec<Attribute> {
+        attrs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn split_on_unescaped_separators() {
+        assert_eq!(
+            split_unescaped(r"CN=a\,b,O=c\+d+UID=e\\,", &[',', '+']),
+            [r"CN=a\,b", r"O=c\+d", r"UID=e\\", ""]
+        );
+        assert_eq!(split_unescaped(r"CN=a\", &[',']), [r"CN=a\"]);
+    }
+
+    #[test]
+    fn unescape_special_characters_and_hex_pairs() {
+        assert_eq!(unescape(r"Acme\, Inc"), "Acme, Inc");
+        assert_eq!(unescape(r#"a\+b\=c\\d\"e"#), r#"a+b=c\d"e"#);
+        assert_eq!(unescape(r"Acme\2C Inc"), "Acme, Inc");
+        assert_eq!(unescape(r"J\C3\B6rg"), "Jörg");
+        // Invalid pairs unescape the next character only
+        assert_eq!(unescape(r"\+f"), "+f");
+        assert_eq!(unescape(r"\4g"), "4g");
+        assert_eq!(unescape(r"\"), "");
+    }
+
+    #[test]
+    fn parse_distinguished_names() {
+        assert_eq!(
+            parse_dn(r"C=ES, O=Acme\, Inc, OU=Ops+UID=svc-a, CN=Service\2DA"),
+            attributes(&[
+                ("2.5.4.6", "es"),
+                ("2.5.4.10", "acme, inc"),
+                ("2.5.4.11", "ops"),
+                ("0.9.2342.19200300.100.1.1", "svc-a"),
+                ("2.5.4.3", "service-a"),
+            ])
+        );
+        // OIDs, lowercase and unknown types
+        assert_eq!(
+            parse_dn("2.5.4.3=a, emailAddress=B@X, foo=c"),
+            attributes(&[
+                ("2.5.4.3", "a"),
+                ("1.2.840.113549.1.9.1", "b@x"),
+                ("FOO", "c"),
+            ])
+        );
+    }
+
+    #[test]
+    fn certificate_attributes() {
+        let certs = client();
+        let cert = certs.leaf();
+        assert_eq!(
+            cert.subject_attributes,
+            parse_dn(r"C=ES, O=Acme\, Inc, OU=Ops+UID=svc-a, CN=Service-A")
+        );
+        assert_eq!(
+            cert.fingerprint(),
+            normalize_fingerprint(CLIENT_FINGERPRINT)
+        );
+        assert_eq!(
+            cert.sans(),
+            [
+                SubjectAltName::Dns("Svc.Internal".to_owned()),
+                SubjectAltName::Ip([10, 0, 0, 7].into()),
+                SubjectAltName::Email("ops@Example.COM".to_owned()),
+                SubjectAltName::Uri("spiffe://example.org/a".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn allowed_subjects() {
+        let certs = client();
+        let cert = certs.leaf();
+        let allowed = |subject: &str| {
+            ClientCertAuth::new()
+                .allow_subject(subject)
+                .is_allowed(cert)
+        };
+
+        assert!(allowed(
+            r"C=ES, O=Acme\, Inc, OU=Ops+UID=svc-a, CN=Service-A"
+        ));
+        assert!(allowed(
+            r"c=es,o=ACME\2C INC,2.5.4.11=ops+uid=SVC-A,cn=service-a"
+        ));
+        // Every attribute must match in order
+        assert!(!allowed("CN=Service-A"));
+        assert!(!allowed(
+            r"CN=Service-A, OU=Ops+UID=svc-a, O=Acme\, Inc, C=ES"
+        ));
+        assert!(!allowed(
+            r"C=ES, O=Acme, Inc, OU=Ops+UID=svc-a, CN=Service-A"
+        ));
+        assert!(!allowed(
+            r"C=ES, O=Acme\, Inc, OU=Ops\+UID=svc-a, CN=Service-A"
+        ));
+        // Unknown types never match
+        assert!(!allowed(
+            r"C=ES, O=Acme\, Inc, OU=Ops+USERID=svc-a, CN=Service-A"
+        ));
+    }
+
+    #[test]
+    fn allowed_sans_and_fingerprints() {
+        let certs = client();
+        let cert = certs.leaf();
+
+        assert!(ClientCertAuth::new().is_allowed(cert));
+        for auth in [
+            ClientCertAuth::new().allow_dns("svc.internal"),
+            ClientCertAuth::new().allow_ip([10, 0, 0, 7].into()),
+            ClientCertAuth::new().allow_email("ops@example.com"),
+            ClientCertAuth::new().allow_uri("spiffe://example.org/a"),
+            ClientCertAuth::new().allow_fingerprint(CLIENT_FINGERPRINT),
+            ClientCertAuth::new().allow_fingerprint(&normalize_fingerprint(CLIENT_FINGERPRINT)),
+            // Any of the lists may match
+            ClientCertAuth::new()
+                .allow_subject("CN=other")
+                .allow_dns("other.internal")
+                .allow_ip([10, 0, 0, 7].into()),
+        ] {
+            assert!(auth.is_allowed(cert), "{:?}", auth);
+        }
+        for auth in [
+            ClientCertAuth::new().allow_dns("other.internal"),
+            ClientCertAuth::new().allow_ip([10, 0, 0, 8].into()),
+            // The local part of emails is case-sensitive
+            ClientCertAuth::new().allow_email("OPS@example.com"),
+            ClientCertAuth::new().allow_uri("spiffe://example.org/b"),
+            ClientCertAuth::new().allow_fingerprint("00:11"),
+        ] {
+            assert!(!auth.is_allowed(cert), "{:?}", auth);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! The response compression module.
+//!
+//! It provides a [`Compression`] middleware which negotiates the `Accept-Encoding` request header
+//! (including q-values) and streams the response body through the chosen encoder.
+//!
+//! Each coding is enabled by its own Cargo feature: `gzip`, `deflate`, `brotli` and `zstd`.
+//!
+//! Responses are left untouched when:
+//!
+//! - They are already encoded (have a `Content-Encoding` header) or have a `Cache-Control: no-transform` directive.
+//! - They have no body (e.g. `204 No Content`, `304 Not Modified` or `HEAD` requests) or are partial (`206 Partial Content`).
+//! - Their `Content-Length` is below the configured threshold.
+//! - Their `Content-Type` is not compressible (e.g. images, videos or archives).
+//!
+//! Otherwise, a `Vary: Accept-Encoding` header is added and, if the client accepts one of the codings,
+//! the body gets compressed, the `Content-Encoding` header is set and the `Content-Length` header is removed.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{Body, Compression, Middlewares, Request, Response, Result};
+//!
+//! let handler = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::from("¡Hola!"))) };
+//!
+//! let mut middlewares = Middlewares::new(handler);
+//! middlewares.link_after(Compression::new().min_size(1024));
+//! ```
+
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Method, StatusCode};
+use std::io;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::encoding::{negotiate, Encoding};
+use crate::headers::add_vary;
+use crate::middleware::AfterMiddleware;
+use crate::{Body, Request, Response, Result};
+
+/// The codings compiled in, in server preference order.
+pub(crate) const ENCODINGS: &[Encoding] = &[
+    #[cfg(feature = "brotli")]
+    Encoding::Brotli,
+    #[cfg(feature = "zstd")]
+    Encoding::Zstd,
+    #[cfg(feature = "gzip")]
+    Encoding::Gzip,
+    #[cfg(feature = "deflate")]
+    Encoding::Deflate,
+];
+
+/// An [`AfterMiddleware`] which compresses response bodies.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    encodings: Vec<Encoding>,
+    min_size: u64,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compression {
+    /// Create a new middleware using every coding enabled via Cargo features
+    /// and a minimum body size of `860` bytes.
+    pub fn new() -> Self {
+        Self {
+            encodings: ENCODINGS.to_vec(),
+            min_size: 860,
+        }
+    }
+
+    /// Set the codings to use in preference order.
+    ///
+    /// Codings whose Cargo feature is not enabled are ignored.
+    pub fn encodings<I>(mut self, encodings: I) -> Self
+    where
+        I: IntoIterator<Item = Encoding>,
+    {
+        self.encodings = encodings
+            .into_iter()
+            .filter(|e| ENCODINGS.contains(e))
+            .collect();
+        self
+    }
+
+    /// Set the minimum `Content-Length` of a response to be compressed.
+    ///
+    /// Responses whose size is unknown (e.g. streamed ones) are always compressed.
+    pub fn min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    // Returns `true` if the response could be compressed regardless of the request `Accept-Encoding`.
+    fn is_compressible(&self, req: &Request, res: &Response) -> bool {
+        let status = res.status();
+        if req.method() == Method::HEAD
+            || status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || status == StatusCode::PARTIAL_CONTENT
+        {
+            return false;
+        }
+
+        let headers = res.headers();
+        if headers.contains_key(header::CONTENT_ENCODING) || has_no_transform(headers) {
+            return false;
+        }
+
+        let length = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            // In-process bodies have no `Content-Length` header yet
+            .or_else(|| hyper::body::HttpBody::size_hint(res.body()).exact());
+        if matches!(length, Some(length) if length < self.min_size) {
+            return false;
+        }
+
+        headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(is_compressible_type)
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl AfterMiddleware for Compression {
+    async fn after(&self, req: &mut Request, mut res: Response) -> Result<Response> {
+        if self.encodings.is_empty() || !self.is_compressible(req, &res) {
+            return Ok(res);
+        }
+
+        let headers = res.headers_mut();
+        add_vary(headers, &["accept-encoding"]);
+
+        let accept = req.headers().get(header::ACCEPT_ENCODING);
+        let encoding = match negotiate(accept, &self.encodings) {
+            Some(encoding) => encoding,
+            None => return Ok(res),
+        };
+
+        headers.insert(header::CONTENT_ENCODING, encoding.to_header_value());
+        headers.remove(header::CONTENT_LENGTH);
+        headers.remove(header::ACCEPT_RANGES);
+        // The representation changes so a strong validator becomes a weak one
+        if let Some(etag) = headers.get(header::ETAG).and_then(|v| v.to_str().ok()) {
+            if !etag.starts_with("W/") {
+                if let Ok(weak) = HeaderValue::from_str(&format!("W/{}", etag)) {
+                    headers.insert(header::ETAG, weak);
+                }
+            }
+        }
+
+        let (parts, body) = res.into_parts();
+        Ok(Response::from_parts(parts, encode(body, encoding)))
+    }
+}
+
+/// Stream a body through the encoder of the given coding.
+pub(crate) fn encode(body: Body, encoding: Encoding) -> Body {
+    let reader = StreamReader::new(body.map_err(|err| io::Error::new(io::ErrorKind::Other, err)));
+
+    match encoding {
+        #[cfg(feature = "gzip")]
+        Encoding::Gzip => {
+            let encoder = async_compression::tokio::bufread::GzipEncoder::new(reader);
+            Body::wrap_stream(ReaderStream::new(encoder))
+        }
+        #[cfg(feature = "deflate")]
+        Encoding::Deflate => {
+            let encoder = async_compression::tokio::bufread::ZlibEncoder::new(reader);
+            Body::wrap_stream(ReaderStream::new(encoder))
+        }
+        #[cfg(feature = "brotli")]
+        Encoding::Brotli => {
+            // The default Brotli quality (11) is too expensive for on-the-fly compression
+            let encoder = async_compression::tokio::bufread::BrotliEncoder::with_quality(
+                reader,
+                async_compression::Level::Precise(4),
+            );
+            Body::wrap_stream(ReaderStream::new(encoder))
+        }
+        #[cfg(feature = "zstd")]
+        Encoding::Zstd => {
+            let encoder = async_compression::tokio::bufread::ZstdEncoder::new(reader);
+            Body::wrap_stream(ReaderStream::new(encoder))
+        }
+        #[allow(unreachable_patterns)]
+        _ => Body::wrap_stream(ReaderStream::new(reader)),
+    }
+}
+
+fn has_no_transform(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-transform"))
+}
+
+fn is_compressible_type(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if essence == "text/event-stream" {
+        return false;
+    }
+
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/wasm"
+                | "application/x-javascript"
+                | "application/manifest+json"
+                | "image/svg+xml"
+                | "image/x-icon"
+                | "font/ttf"
+                | "font/otf"
+        )
+}
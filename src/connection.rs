@@ -0,0 +1,151 @@
+//! The connection information module.
+//!
+//! It provides a [`ConnectionInfo`] trait which extends [`RemoteAddr`] with the local address,
+//! connection ID, accept timestamp and an extensible map of typed values of a connection.
+//!
+//! When a connection is accepted, the [`Service`][`super::Service`] collects its information once and then
+//! inserts it into the extensions of every request of that connection:
+//!
+//! - A [`Connection`] value with the connection ID, accept timestamp, local and remote addresses.
+//! - The remote [`SocketAddr`], if any.
+//! - Every value of the [`ConnectionExtensions`] map (e.g. the [`UnixPeer`][`super::UnixPeer`] of Unix sockets).
+//!
+//! That is, multi-listener servers can tell which address a request arrived on.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{Body, Connection, Request, Response, Result};
+//!
+//! let handler = |req: &mut Request| -> Result<Response> {
+//!     let conn = req.extensions().get::<Connection>().unwrap();
+//!     Ok(Response::new(Body::from(format!(
+//!         "Connection #{} on {:?}",
+//!         conn.id, conn.local_addr
+//!     ))))
+//! };
+//! ```
+
+use hyper::server::conn::AddrStream;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use crate::remote_addr::RemoteAddr;
+use crate::state::States;
+use crate::Request;
+
+/// Defines methods to get the information of a connection.
+///
+/// All the methods are provided so custom connection types (e.g. TLS streams)
+/// only need to override the ones they know about.
+pub trait ConnectionInfo: RemoteAddr {
+    /// Returns the local address this connection was accepted on.
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Returns the ID of this connection.
+    ///
+    /// If `None`, the [`Service`][`super::Service`] assigns a process-wide unique ID.
+    fn connection_id(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the time this connection was accepted.
+    ///
+    /// If `None`, the time the [`Service`][`super::Service`] got the connection is used.
+    fn accepted_at(&self) -> Option<SystemTime> {
+        None
+    }
+
+    /// Returns additional typed values which are inserted into the extensions of every request.
+    fn extensions(&self) -> ConnectionExtensions {
+        ConnectionExtensions::new()
+    }
+}
+
+impl ConnectionInfo for AddrStream {
+    fn local_addr(&self) -> Option<SocketAddr> {
+        Some(self.local_addr())
+    }
+}
+
+/// The information of the connection a request arrived on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connection {
+    /// The connection ID.
+    pub id: u64,
+    /// The time the connection was accepted.
+    pub accepted_at: SystemTime,
+    /// The local address the connection was accepted on, if any.
+    pub local_addr: Option<SocketAddr>,
+    /// The remote (peer) address of the connection, if any.
+    pub remote_addr: Option<SocketAddr>,
+}
+
+impl Connection {
+    pub(crate) fn from_info<T>(conn: &T) -> Self
+    where
+        T: ConnectionInfo + ?Sized,
+    {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self {
+            id: conn
+                .connection_id()
+                .unwrap_or_else(|| NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+            accepted_at: conn.accepted_at().unwrap_or_else(SystemTime::now),
+            local_addr: conn.local_addr(),
+            remote_addr: conn.remote_addr(),
+        }
+    }
+
+    pub(crate) fn apply(&self, req: &mut Request) {
+        req.extensions_mut().insert(*self);
+        if let Some(remote_addr) = self.remote_addr {
+            req.extensions_mut().insert(remote_addr);
+        }
+    }
+}
+
+/// A map of typed values of a connection which are inserted into the extensions of every request.
+#[derive(Default, Clone)]
+pub struct ConnectionExtensions(States);
+
+impl ConnectionExtensions {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a typed value, a value of the same type inserted before is replaced.
+    pub fn insert<T>(&mut self, value: T)
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        self.0.insert_value(value);
+    }
+
+    /// Move all the values of another map into this one.
+    pub fn extend(&mut self, other: ConnectionExtensions) {
+        self.0.extend(other.0);
+    }
+
+    /// Returns `true` if the map contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.0.len() == 0
+    }
+
+    pub(crate) fn apply(&self, req: &mut Request) {
+        self.0.apply(req);
+    }
+}
+
+impl fmt::Debug for ConnectionExtensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionExtensions")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
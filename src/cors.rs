@@ -0,0 +1,436 @@
+//! The Cross-Origin Resource Sharing (CORS) module.
+//!
+//! It provides a configurable [`Cors`] component made of a before and after middleware pair
+//! intended to be plugged in via [`Middlewares::link`][`super::Middlewares::link`].
+//!
+//! - The [`CorsBefore`] middleware answers `OPTIONS` preflight requests directly with a `204 No Content` response
+//!   or a `403 Forbidden` error if the origin, method or headers are not allowed.
+//! - The [`CorsAfter`] middleware adds the CORS headers to the responses (and errors) of allowed origins
+//!   and takes care of the `Vary: Origin` header.
+//!
+//! Allowed origins can be defined as exact values, wildcard patterns (e.g. `https://*.example.com`),
+//! regular expressions (requires the `regex` feature) or custom predicates.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper::Method;
+//! use hyper_middleware::{Body, Cors, Middlewares, Request, Response, Result};
+//! use std::time::Duration;
+//!
+//! let handler = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::empty())) };
+//!
+//! let cors = Cors::new()
+//!     .allow_origin("https://example.com")
+//!     .allow_origin("https://*.example.com")
+//!     .allow_methods(vec![Method::GET, Method::POST, Method::DELETE])
+//!     .allow_headers(vec!["content-type", "authorization"])
+//!     .expose_headers(vec!["x-request-id"])
+//!     .allow_credentials(true)
+//!     .max_age(Duration::from_secs(3600));
+//!
+//! let mut middlewares = Middlewares::new(handler);
+//! middlewares.link(cors.build());
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Method, StatusCode};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::headers::add_vary;
+use crate::middleware::{AfterMiddleware, BeforeMiddleware, BeforeOutcome};
+use crate::{http_error_forbidden, Body, Error, Request, Response, Result};
+
+type OriginPredicate = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+enum AllowOrigin {
+    Exact(String),
+    Wildcard(String, String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+    Predicate(OriginPredicate),
+}
+
+impl AllowOrigin {
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            AllowOrigin::Exact(value) => value.eq_ignore_ascii_case(origin),
+            AllowOrigin::Wildcard(prefix, suffix) => {
+                let origin = origin.to_ascii_lowercase();
+                origin.len() >= prefix.len() + suffix.len()
+                    && origin.starts_with(prefix.as_str())
+                    && origin.ends_with(suffix.as_str())
+            }
+            #[cfg(feature = "regex")]
+            AllowOrigin::Regex(re) => re.is_match(origin),
+            AllowOrigin::Predicate(predicate) => predicate(origin),
+        }
+    }
+}
+
+impl fmt::Debug for AllowOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllowOrigin::Exact(value) => f.debug_tuple("Exact").field(value).finish(),
+            AllowOrigin::Wildcard(prefix, suffix) => f
+                .debug_tuple("Wildcard")
+                .field(prefix)
+                .field(suffix)
+                .finish(),
+            #[cfg(feature = "regex")]
+            AllowOrigin::Regex(re) => f.debug_tuple("Regex").field(re).finish(),
+            AllowOrigin::Predicate(_) => f.write_str("Predicate"),
+        }
+    }
+}
+
+/// The CORS configuration which produces a [`CorsBefore`] and [`CorsAfter`] middleware pair.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct Cors {
+    any_origin: bool,
+    origins: Vec<AllowOrigin>,
+    methods: Vec<Method>,
+    any_header: bool,
+    headers: Vec<HeaderName>,
+    expose_headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cors {
+    /// Create a new CORS configuration which allows no origins
+    /// and the `GET`, `HEAD` and `POST` methods.
+    pub fn new() -> Self {
+        Self {
+            any_origin: false,
+            origins: vec![],
+            methods: vec![Method::GET, Method::HEAD, Method::POST],
+            any_header: false,
+            headers: vec![],
+            expose_headers: vec![],
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Allow requests from any origin.
+    ///
+    /// It can't be combined with [`Cors::allow_credentials`], list the origins
+    /// or use [`Cors::allow_origin_fn`] instead.
+    pub fn allow_any_origin(mut self) -> Self {
+        self.any_origin = true;
+        self
+    }
+
+    /// Allow requests from the given origin.
+    ///
+    /// The origin can contain a single `*` wildcard (e.g. `https://*.example.com`).
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        let origin = origin.trim().to_ascii_lowercase();
+        let allow = match origin.split_once('*') {
+            Some((prefix, suffix)) => AllowOrigin::Wildcard(prefix.to_owned(), suffix.to_owned()),
+            None => AllowOrigin::Exact(origin),
+        };
+        self.origins.push(allow);
+        self
+    }
+
+    /// Allow requests from origins matching the given regular expression.
+    ///
+    /// The expression must match the whole origin, as if it was wrapped in `^(?:...)$`.
+    /// It returns an error if the regular expression is not valid.
+    #[cfg(feature = "regex")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "regex")))]
+    pub fn allow_origin_regex(mut self, pattern: &str) -> Result<Self> {
+        let re =
+            regex::Regex::new(&format!("^(?:{})$", pattern)).map_err(|err| crate::error!(err))?;
+        self.origins.push(AllowOrigin::Regex(re));
+        Ok(self)
+    }
+
+    /// Allow requests from origins satisfying the given predicate.
+    pub fn allow_origin_fn<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origins
+            .push(AllowOrigin::Predicate(Arc::new(predicate)));
+        self
+    }
+
+    /// Set the allowed methods.
+    pub fn allow_methods<I>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = Method>,
+    {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Allow any request header.
+    pub fn allow_any_header(mut self) -> Self {
+        self.any_header = true;
+        self
+    }
+
+    /// Set the allowed request headers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a header name is not valid.
+    pub fn allow_headers<I, K>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        self.headers = header_names(headers);
+        self
+    }
+
+    /// Set the response headers exposed to the client.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a header name is not valid.
+    pub fn expose_headers<I, K>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        self.expose_headers = header_names(headers);
+        self
+    }
+
+    /// Allow requests with credentials (cookies, authorization headers or TLS client certificates).
+    ///
+    /// It can't be combined with [`Cors::allow_any_origin`].
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.credentials = allow;
+        self
+    }
+
+    /// Set how long the results of a preflight request can be cached.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Build the middleware pair to be plugged in via [`Middlewares::link`][`super::Middlewares::link`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if any origin is allowed along with credentials,
+    /// since every site could then make authenticated requests.
+    pub fn build(self) -> (CorsBefore, CorsAfter) {
+        assert!(
+            !(self.any_origin && self.credentials),
+            "credentials can't be allowed for any origin"
+        );
+        let cors = Arc::new(self);
+        (CorsBefore { cors: cors.clone() }, CorsAfter { cors })
+    }
+
+    fn is_allowed_origin(&self, origin: &str) -> bool {
+        self.any_origin || self.origins.iter().any(|o| o.matches(origin))
+    }
+
+    // Returns the origin of an allowed cross-origin request.
+    fn allowed_origin<'a>(&self, req: &'a Request) -> Option<&'a HeaderValue> {
+        let origin = req.headers().get(header::ORIGIN)?;
+        let allowed = origin
+            .to_str()
+            .map(|o| self.is_allowed_origin(o))
+            .unwrap_or(false);
+        if allowed {
+            Some(origin)
+        } else {
+            None
+        }
+    }
+
+    // Returns `true` if the response varies depending on the request origin.
+    fn varies_on_origin(&self) -> bool {
+        !self.any_origin
+    }
+
+    // Add the CORS headers shared by preflight and actual responses.
+    fn apply(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        let allow_origin = if self.varies_on_origin() {
+            origin.clone()
+        } else {
+            HeaderValue::from_static("*")
+        };
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if self.credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    fn preflight(&self, req: &Request) -> Result<Response> {
+        let origin = match self.allowed_origin(req) {
+            Some(origin) => origin,
+            None => return Err(http_error_forbidden!("origin not allowed")),
+        };
+
+        let method = req
+            .headers()
+            .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|v| Method::from_bytes(v.as_bytes()).ok());
+        match method {
+            Some(method) if self.methods.contains(&method) => {}
+            _ => return Err(http_error_forbidden!("method not allowed")),
+        }
+
+        let requested = req
+            .headers()
+            .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let requested: Vec<&str> = requested
+            .split(',')
+            .map(|h| h.trim())
+            .filter(|h| !h.is_empty())
+            .collect();
+        if !self.any_header {
+            let allowed = requested.iter().all(|h| {
+                self.headers
+                    .iter()
+                    .any(|a| a.as_str().eq_ignore_ascii_case(h))
+            });
+            if !allowed {
+                return Err(http_error_forbidden!("headers not allowed"));
+            }
+        }
+
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::NO_CONTENT;
+        let headers = res.headers_mut();
+        self.apply(origin, headers);
+
+        let methods = join(self.methods.iter().map(|m| m.as_str()));
+        insert(headers, header::ACCESS_CONTROL_ALLOW_METHODS, &methods);
+
+        let allow_headers = if self.any_header {
+            requested.join(", ")
+        } else {
+            join(self.headers.iter().map(|h| h.as_str()))
+        };
+        if !allow_headers.is_empty() {
+            insert(
+                headers,
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                &allow_headers,
+            );
+        }
+        if let Some(max_age) = self.max_age {
+            insert(
+                headers,
+                header::ACCESS_CONTROL_MAX_AGE,
+                &max_age.as_secs().to_string(),
+            );
+        }
+        add_vary(
+            headers,
+            &[
+                "origin",
+                "access-control-request-method",
+                "access-control-request-headers",
+            ],
+        );
+
+        Ok(res)
+    }
+
+    fn decorate(&self, req: &Request, headers: &mut HeaderMap) {
+        if let Some(origin) = self.allowed_origin(req) {
+            self.apply(origin, headers);
+            if !self.expose_headers.is_empty() {
+                let expose = join(self.expose_headers.iter().map(|h| h.as_str()));
+                insert(headers, header::ACCESS_CONTROL_EXPOSE_HEADERS, &expose);
+            }
+        }
+        if self.varies_on_origin() {
+            add_vary(headers, &["origin"]);
+        }
+    }
+}
+
+/// The [`BeforeMiddleware`] part of [`Cors`] which answers preflight requests.
+#[derive(Debug, Clone)]
+pub struct CorsBefore {
+    cors: Arc<Cors>,
+}
+
+#[async_trait]
+impl BeforeMiddleware for CorsBefore {
+    async fn intercept(&self, req: &mut Request) -> Result<BeforeOutcome> {
+        let is_preflight = req.method() == Method::OPTIONS
+            && req.headers().contains_key(header::ORIGIN)
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+        if !is_preflight {
+            return Ok(BeforeOutcome::Continue);
+        }
+        self.cors.preflight(req).map(BeforeOutcome::Respond)
+    }
+}
+
+/// The [`AfterMiddleware`] part of [`Cors`] which adds the CORS headers to responses.
+#[derive(Debug, Clone)]
+pub struct CorsAfter {
+    cors: Arc<Cors>,
+}
+
+#[async_trait]
+impl AfterMiddleware for CorsAfter {
+    async fn after(&self, req: &mut Request, mut res: Response) -> Result<Response> {
+        self.cors.decorate(req, res.headers_mut());
+        Ok(res)
+    }
+
+    async fn catch(&self, req: &mut Request, mut err: Error) -> Result<Response> {
+        self.cors.decorate(req, err.headers_mut());
+        Err(err)
+    }
+}
+
+fn header_names<I, K>(headers: I) -> Vec<HeaderName>
+where
+    I: IntoIterator<Item = K>,
+    K: AsRef<str>,
+{
+    headers
+        .into_iter()
+        .map(|h| {
+            let h = h.as_ref();
+            HeaderName::from_bytes(h.as_bytes())
+                .unwrap_or_else(|_| panic!("invalid header name `{}`", h))
+        })
+        .collect()
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a str>) -> String {
+    values.collect::<Vec<&str>>().join(", ")
+}
+
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
@@ -0,0 +1,155 @@
+//! The request decompression module.
+//!
+//! It provides a [`Decompression`] middleware which transparently decodes request bodies
+//! sent with a `Content-Encoding` header, so handlers always read the original payload.
+//!
+//! The codings supported are the ones enabled via Cargo features: `gzip`, `deflate`, `brotli` and `zstd`.
+//!
+//! - Requests using an unknown or disabled coding are rejected with a `415 Unsupported Media Type` error.
+//! - Reading a decoded body fails with a `413 Payload Too Large` error as soon as its size exceeds
+//!   the configured ceiling, which protects handlers from decompression bombs.
+//! - Reading a malformed encoded body fails with a `400 Bad Request` error.
+//!
+//! Once decoded, the `Content-Encoding` and `Content-Length` request headers are removed.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{async_trait, Body, Decompression, Handler, Middlewares, Request, Response, Result};
+//!
+//! struct Application {}
+//!
+//! #[async_trait]
+//! impl Handler for Application {
+//!     async fn handle(&self, req: &mut Request) -> Result<Response> {
+//!         // The body is already decoded here
+//!         let body = hyper::body::to_bytes(std::mem::take(req.body_mut())).await?;
+//!         Ok(Response::new(Body::from(format!("{} bytes received", body.len()))))
+//!     }
+//! }
+//!
+//! let mut middlewares = Middlewares::new(Application {});
+//! // Limit decoded request bodies to 10 MiB
+//! middlewares.link_before(Decompression::new(10 * 1024 * 1024));
+//! ```
+
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use hyper::header::{CONTENT_ENCODING, CONTENT_LENGTH};
+use hyper::StatusCode;
+use std::io;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::body_limit::LimitedBody;
+use crate::compression::ENCODINGS;
+use crate::encoding::Encoding;
+use crate::middleware::BeforeMiddleware;
+use crate::{
+    http_error_bad_request, http_error_unsupported_media_type, Body, Error, Request, Result,
+};
+
+/// A [`BeforeMiddleware`] which decodes compressed request bodies.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct Decompression {
+    max_size: u64,
+}
+
+impl Decompression {
+    /// Create a new middleware which limits decoded request bodies to the given number of bytes.
+    pub fn new(max_size: u64) -> Self {
+        Self { max_size }
+    }
+}
+
+#[async_trait]
+impl BeforeMiddleware for Decompression {
+    async fn before(&self, req: &mut Request) -> Result {
+        let value = match req.headers().get(CONTENT_ENCODING) {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        let value = value
+            .to_str()
+            .map_err(|_| http_error_bad_request!("invalid content-encoding header"))?;
+
+        // Codings are listed in the order they were applied
+        let mut encodings = vec![];
+        for coding in value.split(',').map(str::trim) {
+            if coding.is_empty() || coding.eq_ignore_ascii_case("identity") {
+                continue;
+            }
+            match coding.parse::<Encoding>() {
+                Ok(encoding) if ENCODINGS.contains(&encoding) => encodings.push(encoding),
+                _ => {
+                    return Err(http_error_unsupported_media_type!(
+                        "unsupported content encoding: {}",
+                        coding
+                    ))
+                }
+            }
+        }
+
+        req.headers_mut().remove(CONTENT_ENCODING);
+        if encodings.is_empty() {
+            return Ok(());
+        }
+        req.headers_mut().remove(CONTENT_LENGTH);
+
+        let mut body = std::mem::take(req.body_mut());
+        for encoding in encodings.into_iter().rev() {
+            body = decode(body, encoding);
+        }
+        *req.body_mut() = Body::wrap_stream(LimitedBody::new(body, self.max_size));
+
+        Ok(())
+    }
+}
+
+/// Stream a body through the decoder of the given coding.
+fn decode(body: Body, encoding: Encoding) -> Body {
+    let reader = StreamReader::new(body.map_err(|err| io::Error::new(io::ErrorKind::Other, err)));
+
+    match encoding {
+        #[cfg(feature = "gzip")]
+        Encoding::Gzip => {
+            let decoder = async_compression::tokio::bufread::GzipDecoder::new(reader);
+            Body::wrap_stream(ReaderStream::new(decoder).map_err(decode_error))
+        }
+        #[cfg(feature = "deflate")]
+        Encoding::Deflate => {
+            let decoder = async_compression::tokio::bufread::ZlibDecoder::new(reader);
+            Body::wrap_stream(ReaderStream::new(decoder).map_err(decode_error))
+        }
+        #[cfg(feature = "brotli")]
+        Encoding::Brotli => {
+            let decoder = async_compression::tokio::bufread::BrotliDecoder::new(reader);
+            Body::wrap_stream(ReaderStream::new(decoder).map_err(decode_error))
+        }
+        #[cfg(feature = "zstd")]
+        Encoding::Zstd => {
+            let decoder = async_compression::tokio::bufread::ZstdDecoder::new(reader);
+            Body::wrap_stream(ReaderStream::new(decoder).map_err(decode_error))
+        }
+        #[allow(unreachable_patterns)]
+        _ => Body::wrap_stream(ReaderStream::new(reader).map_err(decode_error)),
+    }
+}
+
+// Preserve errors raised by the inner body (e.g. a body limit) or report a malformed payload otherwise.
+fn decode_error(err: io::Error) -> Error {
+    let is_body_error = err
+        .get_ref()
+        .map(|inner| inner.is::<hyper::Error>())
+        .unwrap_or(false);
+    if is_body_error {
+        if let Some(inner) = err.into_inner() {
+            if let Ok(inner) = inner.downcast::<hyper::Error>() {
+                return Error::from(*inner);
+            }
+        }
+        return http_error_bad_request!("failed to read the request body");
+    }
+    http_error_bad_request!("invalid encoded request body: {}", err)
+}
@@ -0,0 +1,129 @@
+//! The content encoding module.
+//!
+//! It provides the [`Encoding`] type representing the supported HTTP content codings
+//! as well as `Accept-Encoding` negotiation facilities.
+
+use hyper::header::HeaderValue;
+use std::fmt;
+use std::str::FromStr;
+
+/// An HTTP content coding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// The `gzip` coding.
+    Gzip,
+    /// The `deflate` coding.
+    Deflate,
+    /// The `br` (Brotli) coding.
+    Brotli,
+    /// The `zstd` (Zstandard) coding.
+    Zstd,
+}
+
+impl Encoding {
+    /// Returns the coding name as used by the `Accept-Encoding` and `Content-Encoding` headers.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    /// Returns the conventional file extension of the coding (e.g. `gz` for `gzip`).
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gz",
+            Encoding::Deflate => "zz",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zst",
+        }
+    }
+
+    /// Returns the header value representation of the coding.
+    pub fn to_header_value(self) -> HeaderValue {
+        HeaderValue::from_static(self.as_str())
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Ok(Encoding::Gzip),
+            "deflate" => Ok(Encoding::Deflate),
+            "br" => Ok(Encoding::Brotli),
+            "zstd" => Ok(Encoding::Zstd),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Choose the best coding for an `Accept-Encoding` header value.
+///
+/// The candidates are given in server preference order, which breaks ties between equal q-values.
+/// Returns `None` if the client does not accept any of the candidates.
+pub fn negotiate(
+    accept_encoding: Option<&HeaderValue>,
+    candidates: &[Encoding],
+) -> Option<Encoding> {
+    let accept = accept_encoding.and_then(|v| v.to_str().ok())?;
+
+    // Parse the list of `coding;q=value` entries
+    let entries: Vec<(String, f32)> = accept
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let coding = parts.next()?.trim().to_ascii_lowercase();
+            if coding.is_empty() {
+                return None;
+            }
+            let mut q = 1.0;
+            for param in parts {
+                let mut kv = param.splitn(2, '=');
+                if kv.next().map(|k| k.trim().eq_ignore_ascii_case("q")) == Some(true) {
+                    q = kv
+                        .next()
+                        .and_then(|v| v.trim().parse::<f32>().ok())
+                        .unwrap_or(0.0);
+                }
+            }
+            Some((coding, q))
+        })
+        .collect();
+
+    let quality = |encoding: &Encoding| -> f32 {
+        let explicit = entries
+            .iter()
+            .find(|(coding, _)| coding.parse::<Encoding>().ok() == Some(*encoding));
+        match explicit {
+            Some((_, q)) => *q,
+            None => entries
+                .iter()
+                .find(|(coding, _)| coding == "*")
+                .map(|(_, q)| *q)
+                .unwrap_or(0.0),
+        }
+    };
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in candidates {
+        let q = quality(encoding);
+        if q <= 0.0 {
+            continue;
+        }
+        match best {
+            Some((_, best_q)) if best_q >= q => {}
+            _ => best = Some((*encoding, q)),
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
@@ -159,6 +159,14 @@ macro_rules! http_error_expectation_failed {
     }}
 }
 
+/// Constructs an [`Error`][`super::Error`] with [`hyper::StatusCode::UNPROCESSABLE_ENTITY`] from a string or existing non-anyhow error value.
+#[macro_export]
+macro_rules! http_error_unprocessable_entity {
+    ($($arg:tt)*) => {{
+        $crate::error!($($arg)*).with_status(StatusCode::UNPROCESSABLE_ENTITY)
+    }}
+}
+
 //  50x
 /// Constructs an [`Error`][`super::Error`] with [`hyper::StatusCode::INTERNAL_SERVER_ERROR`] from a string or existing non-anyhow error value.
 #[macro_export]
@@ -28,14 +28,51 @@
 //! let err = http_error_unauthorized!("user or password does not match");
 //! ```
 //!
+//! c. Render an [`Error`][`super::Error`] as a custom HTTP response via an [`ErrorRenderer`][`super::ErrorRenderer`].
+//!
+//! ```rust
+//! use hyper::{header, StatusCode};
+//! use hyper_middleware::{async_trait, Body, Error, ErrorRenderer, Request, Response, Service};
+//!
+//! struct JsonErrorRenderer {}
+//!
+//! #[async_trait]
+//! impl ErrorRenderer for JsonErrorRenderer {
+//!     async fn render(&self, _req: &mut Request, err: Error) -> Response {
+//!         let status = err.status().unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+//!         let body = format!("{{\"status\":{}}}", status.as_u16());
+//!         let mut res = Response::new(Body::from(body));
+//!         *res.status_mut() = status;
+//!         res.headers_mut().insert(
+//!             header::CONTENT_TYPE,
+//!             header::HeaderValue::from_static("application/json"),
+//!         );
+//!         res
+//!     }
+//! }
+//!
+//! let handler = |_: &mut Request| -> hyper_middleware::Result<Response> {
+//!     Err(hyper_middleware::http_error_not_found!("page not found"))
+//! };
+//! let service = Service::new(handler).with_error_renderer(JsonErrorRenderer {});
+//! ```
+//!
 
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
 use hyper::StatusCode;
 use std::fmt;
 use thiserror::Error as ThisError;
 
+use crate::request_id::RequestId;
+
 /// Macros that provide several facilities for working with HTTP response errors or error casting.
 pub mod macros;
 
+/// Error renderers which convert an [`Error`] into an HTTP response.
+pub mod renderer;
+
+pub use renderer::{DefaultErrorRenderer, ErrorRenderer};
+
 /// `Result<T, Error>`
 ///
 /// An alias of [anyhow::Result][`anyhow::Result`] with defaults.
@@ -48,6 +85,9 @@ pub use anyhow::Context;
 pub struct Error {
     source: anyhow::Error,
     status: Option<StatusCode>,
+    headers: HeaderMap,
+    // Boxed to keep the error small
+    request_id: Option<Box<RequestId>>,
 }
 
 impl Error {
@@ -66,6 +106,37 @@ impl Error {
         self.status = Some(status);
         self
     }
+
+    /// Returns the HTTP headers which should be sent along with the error response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    // Used by middlewares which need to append headers (e.g. `Vary`) instead of replacing them.
+    pub(crate) fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Adds/updates an HTTP header which should be sent along with the error response.
+    ///
+    /// For instance, a `405 Method Not Allowed` error should provide an `Allow` header.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Returns the ID of the request which caused the error, if known.
+    ///
+    /// It's attached by the [`RequestIds`][`crate::RequestIds`] middleware so error logs can be correlated.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref().map(RequestId::as_str)
+    }
+
+    /// Adds/updates the ID of the request which caused the error.
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(Box::new(RequestId::new(request_id)));
+        self
+    }
 }
 
 impl fmt::Display for Error {
@@ -76,10 +147,30 @@ impl fmt::Display for Error {
 
 impl From<hyper::Error> for Error {
     /// Converts a [`hyper::Error`] type into an HTTP [`Error`].
+    ///
+    /// If the Hyper error was caused by an HTTP [`Error`] (e.g. raised by a body stream)
+    /// then its HTTP Status Code is preserved.
     fn from(source: hyper::Error) -> Self {
+        let status = std::error::Error::source(&source)
+            .and_then(|cause| cause.downcast_ref::<Error>())
+            .and_then(|cause| cause.status);
+        Self {
+            source: anyhow::anyhow!(source),
+            status,
+            headers: HeaderMap::new(),
+            request_id: None,
+        }
+    }
+}
+
+impl From<hyper::http::Error> for Error {
+    /// Converts a [`hyper::http::Error`] type (e.g. an HTTP builder error) into an HTTP [`Error`].
+    fn from(source: hyper::http::Error) -> Self {
         Self {
             source: anyhow::anyhow!(source),
             status: None,
+            headers: HeaderMap::new(),
+            request_id: None,
         }
     }
 }
@@ -90,6 +181,8 @@ impl From<std::io::Error> for Error {
         Self {
             source: anyhow::anyhow!(source),
             status: None,
+            headers: HeaderMap::new(),
+            request_id: None,
         }
     }
 }
@@ -100,6 +193,8 @@ impl From<anyhow::Error> for Error {
         Self {
             source,
             status: None,
+            headers: HeaderMap::new(),
+            request_id: None,
         }
     }
 }
@@ -110,6 +205,8 @@ impl From<&str> for Error {
         Self {
             source: anyhow::anyhow!(source.to_owned()),
             status: None,
+            headers: HeaderMap::new(),
+            request_id: None,
         }
     }
 }
@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use hyper::{header, StatusCode};
+
+use crate::error::Error;
+use crate::http::{Body, Request, Response};
+
+#[async_trait]
+/// `ErrorRenderer`s are responsible for creating the `Response` sent back to the client
+/// when the hosted `Handler` fails.
+///
+/// It can be implemented in order to provide custom error pages (e.g. HTML or JSON).
+pub trait ErrorRenderer: Send + Sync + 'static {
+    /// Produce a `Response` from an `Error` returned by the `Handler`.
+    async fn render(&self, req: &mut Request, err: Error) -> Response;
+}
+
+#[async_trait]
+impl<F> ErrorRenderer for F
+where
+    F: Send + Sync + 'static + Fn(&mut Request, Error) -> Response,
+{
+    async fn render(&self, req: &mut Request, err: Error) -> Response {
+        (*self)(req, err)
+    }
+}
+
+#[async_trait]
+impl ErrorRenderer for Box<dyn ErrorRenderer> {
+    async fn render(&self, req: &mut Request, err: Error) -> Response {
+        (**self).render(req, err).await
+    }
+}
+
+/// The default [`ErrorRenderer`] used by the [`Service`][`crate::Service`].
+///
+/// It responds with the HTTP status associated with the error or `500 Internal Server Error` otherwise.
+/// The headers attached to the error are sent along as well.
+/// The body is a plain-text message. Errors with no associated status only expose
+/// the canonical reason of the status code in order to avoid leaking internal details.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultErrorRenderer;
+
+#[async_trait]
+impl ErrorRenderer for DefaultErrorRenderer {
+    async fn render(&self, _: &mut Request, err: Error) -> Response {
+        let (status, message) = match err.status() {
+            Some(status) => (status, err.to_string()),
+            None => {
+                let status = StatusCode::INTERNAL_SERVER_ERROR;
+                let reason = status.canonical_reason().unwrap_or_default();
+                (status, reason.to_owned())
+            }
+        };
+
+        let mut res = Response::new(Body::from(message));
+        *res.status_mut() = status;
+        res.headers_mut().extend(err.headers().clone());
+        res.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+        res
+    }
+}
@@ -0,0 +1,272 @@
+//! The request extractors module.
+//!
+//! It provides a set of extractors which parse typed values out of a [`Request`].
+//! Extraction failures are returned as [`Error`][`crate::Error`]s with an appropriate HTTP status code
+//! so they become proper responses through the middlewares chain.
+//!
+//! - [`Header`]: a header value parsed via [`FromStr`] (`400 Bad Request` if missing or invalid).
+//! - `Query`: the URI query string (`400 Bad Request`). Requires the `extract` feature.
+//! - `Path`: the parameters captured by a [`Router`][`crate::Router`] (`400 Bad Request`). Requires the `extract` feature.
+//! - `Form`: an `application/x-www-form-urlencoded` body (`415 Unsupported Media Type` or `422 Unprocessable Entity`).
+//!   Requires the `extract` feature.
+//! - `Json`: an `application/json` body (`415 Unsupported Media Type`, `400 Bad Request` or `422 Unprocessable Entity`).
+//!   Requires the `json` feature.
+//!
+//! Note that the body extractors take the request body, so only one of them can be used per request.
+//!
+//! ## Example
+//!
+//! ```rust
+//! # #[cfg(feature = "json")]
+//! # mod example {
+//! use hyper_middleware::{async_trait, Body, FromRequest, Handler, Json, Query, Request, Response, Result};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Pagination {
+//!     page: u32,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct NewUser {
+//!     name: String,
+//! }
+//!
+//! struct Application {}
+//!
+//! #[async_trait]
+//! impl Handler for Application {
+//!     async fn handle(&self, req: &mut Request) -> Result<Response> {
+//!         let Query(pagination) = Query::<Pagination>::from_request(req).await?;
+//!         let Json(user) = Json::<NewUser>::from_request(req).await?;
+//!         Ok(Response::new(Body::from(format!("{} (page {})", user.name, pagination.page))))
+//!     }
+//! }
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use hyper::StatusCode;
+use std::str::FromStr;
+
+use crate::http_error_bad_request;
+use crate::{Request, Result};
+
+#[async_trait]
+/// Types that can be created from a [`Request`].
+pub trait FromRequest: Sized {
+    /// Extract a value from the given `Request`.
+    async fn from_request(req: &mut Request) -> Result<Self>;
+}
+
+/// Extracts a header value parsed via [`FromStr`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header<T>(pub T);
+
+impl<T> Header<T>
+where
+    T: FromStr,
+{
+    /// Extract a header value by its name.
+    ///
+    /// It returns a `400 Bad Request` error if the header is missing or cannot be parsed.
+    pub fn from_request(req: &Request, name: &str) -> Result<Self> {
+        let value = match req.headers().get(name) {
+            Some(value) => value,
+            None => return Err(http_error_bad_request!("missing header `{}`", name)),
+        };
+        let value = value.to_str().ok().and_then(|v| T::from_str(v.trim()).ok());
+        match value {
+            Some(value) => Ok(Self(value)),
+            None => Err(http_error_bad_request!("invalid header `{}`", name)),
+        }
+    }
+
+    /// Consumes the extractor returning the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+#[cfg(feature = "extract")]
+#[cfg_attr(docsrs, doc(cfg(feature = "extract")))]
+pub use self::serde_extract::{Form, Path, Query};
+
+#[cfg(feature = "json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+pub use self::json::Json;
+
+// Returns `true` if the request `Content-Type` essence matches the given predicate.
+#[cfg(feature = "extract")]
+fn has_content_type(req: &Request, predicate: impl Fn(&str) -> bool) -> bool {
+    req.headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| predicate(&v.trim().to_ascii_lowercase()))
+        .unwrap_or(false)
+}
+
+// Take and buffer the request body.
+#[cfg(feature = "extract")]
+async fn take_body(req: &mut Request) -> Result<hyper::body::Bytes> {
+    let body = std::mem::take(req.body_mut());
+    Ok(hyper::body::to_bytes(body).await?)
+}
+
+#[cfg(feature = "extract")]
+mod serde_extract {
+    use async_trait::async_trait;
+    use hyper::StatusCode;
+    use serde::de::DeserializeOwned;
+
+    use super::{has_content_type, take_body, FromRequest};
+    use crate::router::Params;
+    use crate::{
+        http_error_bad_request, http_error_unprocessable_entity, http_error_unsupported_media_type,
+    };
+    use crate::{Request, Result};
+
+    /// Extracts the URI query string deserialized via [`serde`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Query<T>(pub T);
+
+    impl<T> Query<T> {
+        /// Consumes the extractor returning the inner value.
+        pub fn into_inner(self) -> T {
+            self.0
+        }
+    }
+
+    #[async_trait]
+    impl<T> FromRequest for Query<T>
+    where
+        T: DeserializeOwned,
+    {
+        async fn from_request(req: &mut Request) -> Result<Self> {
+            let query = req.uri().query().unwrap_or_default();
+            match serde_urlencoded::from_str(query) {
+                Ok(value) => Ok(Self(value)),
+                Err(err) => Err(http_error_bad_request!("invalid query string: {}", err)),
+            }
+        }
+    }
+
+    /// Extracts the [`Params`] captured by a [`Router`][`crate::Router`] deserialized via [`serde`].
+    ///
+    /// The type should be a struct whose fields are named after the route parameters.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Path<T>(pub T);
+
+    impl<T> Path<T> {
+        /// Consumes the extractor returning the inner value.
+        pub fn into_inner(self) -> T {
+            self.0
+        }
+    }
+
+    #[async_trait]
+    impl<T> FromRequest for Path<T>
+    where
+        T: DeserializeOwned,
+    {
+        async fn from_request(req: &mut Request) -> Result<Self> {
+            let params: Vec<(String, String)> = match req.extensions().get::<Params>() {
+                Some(params) => params
+                    .iter()
+                    .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                    .collect(),
+                None => vec![],
+            };
+            // Re-use the url-encoded deserializer which takes care of parsing primitive values
+            let encoded = serde_urlencoded::to_string(params)
+                .map_err(|err| http_error_bad_request!("invalid path parameters: {}", err))?;
+            match serde_urlencoded::from_str(&encoded) {
+                Ok(value) => Ok(Self(value)),
+                Err(err) => Err(http_error_bad_request!("invalid path parameters: {}", err)),
+            }
+        }
+    }
+
+    /// Extracts an `application/x-www-form-urlencoded` request body deserialized via [`serde`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Form<T>(pub T);
+
+    impl<T> Form<T> {
+        /// Consumes the extractor returning the inner value.
+        pub fn into_inner(self) -> T {
+            self.0
+        }
+    }
+
+    #[async_trait]
+    impl<T> FromRequest for Form<T>
+    where
+        T: DeserializeOwned,
+    {
+        async fn from_request(req: &mut Request) -> Result<Self> {
+            if !has_content_type(req, |v| v == "application/x-www-form-urlencoded") {
+                return Err(http_error_unsupported_media_type!(
+                    "expected an `application/x-www-form-urlencoded` request body"
+                ));
+            }
+            let body = take_body(req).await?;
+            match serde_urlencoded::from_bytes(&body) {
+                Ok(value) => Ok(Self(value)),
+                Err(err) => Err(http_error_unprocessable_entity!(
+                    "invalid form data: {}",
+                    err
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use async_trait::async_trait;
+    use hyper::StatusCode;
+    use serde::de::DeserializeOwned;
+    use serde_json::error::Category;
+
+    use super::{has_content_type, take_body, FromRequest};
+    use crate::{
+        http_error_bad_request, http_error_unprocessable_entity, http_error_unsupported_media_type,
+    };
+    use crate::{Request, Result};
+
+    /// Extracts an `application/json` request body deserialized via [`serde`].
+    ///
+    /// Media types with a `+json` suffix (e.g. `application/problem+json`) are accepted as well.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Json<T>(pub T);
+
+    impl<T> Json<T> {
+        /// Consumes the extractor returning the inner value.
+        pub fn into_inner(self) -> T {
+            self.0
+        }
+    }
+
+    #[async_trait]
+    impl<T> FromRequest for Json<T>
+    where
+        T: DeserializeOwned,
+    {
+        async fn from_request(req: &mut Request) -> Result<Self> {
+            if !has_content_type(req, |v| v == "application/json" || v.ends_with("+json")) {
+                return Err(http_error_unsupported_media_type!(
+                    "expected an `application/json` request body"
+                ));
+            }
+            let body = take_body(req).await?;
+            match serde_json::from_slice(&body) {
+                Ok(value) => Ok(Self(value)),
+                Err(err) if err.classify() == Category::Data => Err(
+                    http_error_unprocessable_entity!("invalid JSON data: {}", err),
+                ),
+                Err(err) => Err(http_error_bad_request!("malformed JSON: {}", err)),
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! The trusted proxies module.
+//!
+//! It provides a [`TrustedProxies`] middleware which resolves the real client information of requests
+//! forwarded by reverse proxies or load balancers and stores it in the request extensions as [`ClientInfo`].
+//!
+//! The forwarding headers are only taken into account if the peer address of the connection
+//! (the [`SocketAddr`] inserted by the [`Service`][`super::Service`]) belongs to one of the trusted proxy ranges.
+//! In that case, the `Forwarded` header ([RFC 7239](https://www.rfc-editor.org/rfc/rfc7239)) is used
+//! or the `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host` headers otherwise.
+//!
+//! The list of forwarded addresses is walked from right to left skipping the trusted proxies,
+//! so the first address which is not trusted is the client one. That is, clients can't spoof their
+//! address by sending forwarding headers themselves.
+//!
+//! The raw peer [`SocketAddr`] is left intact in the request extensions.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{Body, ClientInfo, Middlewares, Request, Response, Result, TrustedProxies};
+//!
+//! # fn main() -> Result {
+//! let handler = |req: &mut Request| -> Result<Response> {
+//!     let client = req.extensions().get::<ClientInfo>().unwrap();
+//!     Ok(Response::new(Body::from(format!("{:?} via {}", client.ip, client.scheme))))
+//! };
+//!
+//! let proxies = TrustedProxies::new(vec!["10.0.0.0/8".parse()?, "::1".parse()?]);
+//!
+//! let mut middlewares = Middlewares::new(handler);
+//! middlewares.link_before(proxies);
+//! # Ok(())
+//! # }
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderMap, HeaderName};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use crate::middleware::BeforeMiddleware;
+use crate::{Error, Request, Result};
+
+/// The real client information of a request resolved by [`TrustedProxies`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    /// The client IP address, if known.
+    ///
+    /// It is `None` if the client is forwarded as `unknown` or by an obfuscated identifier.
+    pub ip: Option<IpAddr>,
+    /// The scheme used by the client (e.g. `https`).
+    pub scheme: String,
+    /// The host requested by the client, if known.
+    pub host: Option<String>,
+}
+
+/// An IP address range in CIDR notation (e.g. `10.0.0.0/8` or `fd00::/8`).
+///
+/// A single address (e.g. `127.0.0.1`) is a range of one address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Create a new range from a network address and a prefix length.
+    ///
+    /// It returns an error if the prefix length is longer than the address.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > max {
+            return Err(crate::error!(
+                "invalid prefix length `{}` for `{}`",
+                prefix,
+                addr
+            ));
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    /// Returns `true` if the range contains the given address.
+    ///
+    /// IPv4-mapped IPv6 addresses (e.g. `::ffff:10.0.0.1`) are matched as IPv4 ones.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, canonical(*ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = mask_u32(self.prefix);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = mask_u128(self.prefix);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|err| crate::error!("invalid CIDR `{}`: {}", s, err))?;
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .map_err(|err| crate::error!("invalid CIDR `{}`: {}", s, err))?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+        Cidr::new(canonical(addr), prefix)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// A [`BeforeMiddleware`] which resolves the [`ClientInfo`] of requests forwarded by trusted proxies.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct TrustedProxies {
+    proxies: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    /// Create a new middleware which trusts the given proxy ranges.
+    pub fn new<I>(proxies: I) -> Self
+    where
+        I: IntoIterator<Item = Cidr>,
+    {
+        Self {
+            proxies: proxies.into_iter().collect(),
+        }
+    }
+
+    /// Returns `true` if the given address belongs to a trusted proxy.
+    pub fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.proxies.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// Resolve the client information of a request.
+    pub fn resolve(&self, req: &Request) -> ClientInfo {
+        let peer = req.extensions().get::<SocketAddr>().map(|addr| addr.ip());
+        let mut info = ClientInfo {
+            ip: peer.map(canonical),
+            scheme: req.uri().scheme_str().unwrap_or("http").to_owned(),
+            host: req
+                .headers()
+                .get(header::HOST)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned)
+                .or_else(|| req.uri().authority().map(|a| a.to_string())),
+        };
+
+        let peer = match peer {
+            Some(peer) if self.is_trusted(&peer) => peer,
+            _ => return info,
+        };
+
+        let hops = match forwarded(req.headers()) {
+            Some(hops) => hops,
+            None => x_forwarded(req.headers()),
+        };
+
+        // Walk from the closest hop, skipping the trusted proxies
+        let mut client = None;
+        let mut last_ip = Some(peer);
+        for hop in hops.iter().rev() {
+            client = Some(hop);
+            last_ip = hop.ip;
+            match hop.ip {
+                Some(ip) if self.is_trusted(&ip) => {}
+                // Unknown or obfuscated identifiers can't be verified, so the client IP is unknown
+                _ => break,
+            }
+        }
+
+        if let Some(hop) = client {
+            info.ip = last_ip.map(canonical);
+            if let Some(proto) = &hop.proto {
+                info.scheme = proto.to_ascii_lowercase();
+            }
+            if let Some(host) = &hop.host {
+                info.host = Some(host.clone());
+            }
+        }
+        info
+    }
+}
+
+#[async_trait]
+impl BeforeMiddleware for TrustedProxies {
+    async fn before(&self, req: &mut Request) -> Result {
+        let info = self.resolve(req);
+        req.extensions_mut().insert(info);
+        Ok(())
+    }
+}
+
+// A forwarding hop.
+#[derive(Debug, Default)]
+struct Hop {
+    ip: Option<IpAddr>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+// Parse the `Forwarded` header elements, returns `None` if there is no such header.
+fn forwarded(headers: &HeaderMap) -> Option<Vec<Hop>> {
+    let mut values = headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .peekable();
+    values.peek()?;
+
+    let mut hops = vec![];
+    for value in values {
+        for element in split_quoted(value, ',') {
+            let mut hop = Hop::default();
+            for pair in split_quoted(element, ';') {
+                let (key, value) = match pair.split_once('=') {
+                    Some((key, value)) => (key.trim(), unquote(value.trim())),
+                    None => continue,
+                };
+                if key.eq_ignore_ascii_case("for") {
+                    hop.ip = parse_node(&value);
+                } else if key.eq_ignore_ascii_case("proto") {
+                    hop.proto = Some(value);
+                } else if key.eq_ignore_ascii_case("host") {
+                    hop.host = Some(value);
+                }
+            }
+            hops.push(hop);
+        }
+    }
+    Some(hops)
+}
+
+// Build the hops from the `X-Forwarded-*` headers.
+fn x_forwarded(headers: &HeaderMap) -> Vec<Hop> {
+    let list = |name: &'static str| -> Vec<String> {
+        headers
+            .get_all(HeaderName::from_static(name))
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|v| v.trim().to_owned())
+            .filter(|v| !v.is_empty())
+            .collect()
+    };
+    let ips = list("x-forwarded-for");
+    let protos = list("x-forwarded-proto");
+    let hosts = list("x-forwarded-host");
+
+    // Proxies usually set a single protocol and host, otherwise they are aligned with the addresses
+    let pick = |values: &[String], i: usize| -> Option<String> {
+        if values.len() == ips.len() {
+            values.get(i).cloned()
+        } else {
+            values.first().cloned()
+        }
+    };
+
+    ips.iter()
+        .enumerate()
+        .map(|(i, ip)| Hop {
+            ip: parse_node(ip),
+            proto: pick(&protos, i),
+            host: pick(&hosts, i),
+        })
+        .collect()
+}
+
+// Parse a node identifier like `192.0.2.43`, `192.0.2.43:47011` or `[2001:db8::17]:4711`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim();
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')
+        .and_then(|rest| rest.split(']').next())
+        .and_then(|ip| ip.parse::<Ipv6Addr>().ok())
+        .map(IpAddr::V6)
+}
+
+// Split a header value by a separator ignoring the ones within quoted strings.
+fn split_quoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(value[start..].trim());
+    parts.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
+fn unquote(value: &str) -> String {
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(inner) => {
+            let mut unquoted = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unquoted.extend(chars.next()),
+                    c => unquoted.push(c),
+                }
+            }
+            unquoted
+        }
+        None => value.to_owned(),
+    }
+}
+
+// Convert IPv4-mapped IPv6 addresses into IPv4 ones.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, hi, lo] => {
+                IpAddr::V4(Ipv4Addr::from((u32::from(hi) << 16) | u32::from(lo)))
+            }
+            _ => IpAddr::V6(v6),
+        },
+        ip => ip,
+    }
+}
+
+fn mask_u32(prefix: u8) -> u32 {
+    match prefix {
+        0 => 0,
+        p => u32::MAX << (32 - u32::from(p)),
+    }
+}
+
+fn mask_u128(prefix: u8) -> u128 {
+    match prefix {
+        0 => 0,
+        p => u128::MAX << (128 - u32::from(p)),
+    }
+}
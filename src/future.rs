@@ -0,0 +1,111 @@
+//! Internal future utilities.
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use hyper::server::accept::Accept;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Resolves with the output of the first future which completes, dropping the other one.
+pub(crate) fn race<A, B, T>(a: A, b: B) -> Race<A, B>
+where
+    A: Future<Output = T>,
+    B: Future<Output = T>,
+{
+    Race {
+        a: Box::pin(a),
+        b: Box::pin(b),
+    }
+}
+
+/// Future returned by [`race`].
+pub(crate) struct Race<A, B> {
+    a: Pin<Box<A>>,
+    b: Pin<Box<B>>,
+}
+
+impl<A, B, T> Future for Race<A, B>
+where
+    A: Future<Output = T>,
+    B: Future<Output = T>,
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        if let Poll::Ready(out) = self.a.as_mut().poll(cx) {
+            return Poll::Ready(out);
+        }
+        self.b.as_mut().poll(cx)
+    }
+}
+
+/// Accepts connections from a Hyper [`Accept`] and runs their handshakes concurrently,
+/// so slow connections don't hold back the other ones.
+///
+/// Connections whose handshake fails are dropped.
+pub(crate) struct Handshakes<I, T>
+where
+    I: Accept,
+{
+    incoming: I,
+    done: bool,
+    pending: FuturesUnordered<BoxFuture<'static, io::Result<T>>>,
+}
+
+impl<I, T> Handshakes<I, T>
+where
+    I: Accept,
+{
+    pub(crate) fn new(incoming: I) -> Self {
+        Self {
+            incoming,
+            done: false,
+            pending: FuturesUnordered::new(),
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &I {
+        &self.incoming
+    }
+}
+
+impl<I, T> Handshakes<I, T>
+where
+    I: Accept + Unpin,
+{
+    /// Poll the next connection whose handshake completed.
+    ///
+    /// The `handshake` function starts the handshake of every newly accepted connection.
+    pub(crate) fn poll_accept<F>(
+        &mut self,
+        cx: &mut Context<'_>,
+        _kind: &'static str,
+        mut handshake: F,
+    ) -> Poll<Option<Result<T, I::Error>>>
+    where
+        F: FnMut(I::Conn) -> BoxFuture<'static, io::Result<T>>,
+    {
+        while !self.done {
+            match Pin::new(&mut self.incoming).poll_accept(cx) {
+                Poll::Ready(Some(Ok(conn))) => self.pending.push(handshake(conn)),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => self.done = true,
+                Poll::Pending => break,
+            }
+        }
+
+        loop {
+            match self.pending.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(conn))) => return Poll::Ready(Some(Ok(conn))),
+                Poll::Ready(Some(Err(_err))) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(error = %_err, "{} failed", _kind);
+                }
+                Poll::Ready(None) if self.done => return Poll::Ready(None),
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
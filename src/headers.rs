@@ -0,0 +1,22 @@
+//! Internal HTTP header utilities.
+
+use hyper::header::{self, HeaderMap, HeaderValue};
+
+/// Appends the given values to the `Vary` header unless they are already present.
+pub(crate) fn add_vary(headers: &mut HeaderMap, values: &[&'static str]) {
+    let existing: Vec<String> = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_ascii_lowercase())
+        .collect();
+    if existing.iter().any(|v| v == "*") {
+        return;
+    }
+    for value in values {
+        if !existing.iter().any(|v| v == value) {
+            headers.append(header::VARY, HeaderValue::from_static(value));
+        }
+    }
+}
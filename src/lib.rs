@@ -13,7 +13,28 @@
 //! ## Features
 //!
 //! - Compact [Middleware & Handler System][`middleware`] inspired by [The Iron Framework](https://github.com/iron/iron).
+//! - Path [`Router`] with parameters, wildcards, method matching and nested mounts.
 //! - Simple [Hyper Service][`hyper::service::Service`] with [Remote Address][`hyper::server::conn::AddrStream`] access.
+//! - [Connection information][`ConnectionInfo`] (local address, ID, accept time) in every request.
+//! - Request [extractors][`extract`] for headers, query strings, forms, JSON and path parameters.
+//! - [Response helpers][`ResponseExt`] for JSON, HTML, text, redirects and files.
+//! - Typed application [`State`] shared with every request.
+//! - Request [`BodyLimit`] middleware.
+//! - Per-request [`Timeout`] middleware.
+//! - [`Cors`] middleware with preflight handling.
+//! - Response compression middleware (`gzip`, `deflate`, `brotli` and `zstd` features).
+//! - Request body decompression middleware.
+//! - [`StaticFiles`] handler with range, conditional requests and pre-compressed files support.
+//! - [`AccessLog`] middleware with Common, Combined and JSON formats.
+//! - Prometheus [`Metrics`] middleware and exposition handler.
+//! - [`RequestId`] generation and propagation middleware.
+//! - [`TrustedProxies`] middleware resolving the real [`ClientInfo`] from `Forwarded` and `X-Forwarded-*` headers.
+//! - [PROXY protocol][`ProxyProtocol`] v1 and v2 support on accepted connections.
+//! - Serving over Unix domain sockets via [`UnixIncoming`] with peer credentials access (Unix only).
+//! - TLS termination with SNI, ALPN and certificates hot reload via [rustls](https://github.com/rustls/rustls) (`rustls` feature).
+//! - Mutual TLS `ClientCertAuth` client certificate authentication middleware (`rustls` feature).
+//! - Graceful [`Shutdown`] with connection draining.
+//! - Request and middleware spans via [tracing](https://github.com/tokio-rs/tracing) (`tracing` feature).
 //! - Convenient [`Error`] and [`Result`] types powered by [anyhow](https://github.com/dtolnay/anyhow).
 //! - `Async` support via [async-trait](https://github.com/dtolnay/async-trait).
 //! - Macros to facilitate HTTP response errors or error casting.
@@ -21,17 +42,77 @@
 //! Check it out [`middleware`] module for more details.
 //!
 
+pub mod access_log;
+pub mod body_limit;
+#[cfg(feature = "rustls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+pub mod client_cert;
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub mod compression;
+pub mod connection;
+pub mod cors;
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub mod decompression;
+pub mod encoding;
 pub mod error;
+pub mod extract;
+pub mod forwarded;
+mod future;
+mod headers;
 pub mod http;
+pub mod metrics;
 pub mod middleware;
+pub mod proxy_protocol;
 pub mod remote_addr;
+pub mod request_id;
+pub mod response;
+pub mod router;
 pub mod service;
+pub mod shutdown;
+pub mod state;
+pub mod static_files;
+pub mod timeout;
+#[cfg(feature = "rustls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+pub mod tls;
+mod trace;
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+pub mod unix;
 
-pub use error::{Context, Error, Result};
+pub use access_log::{AccessLog, AccessLogAfter, AccessLogBefore, LogFormat, LogSink, StdoutSink};
+pub use body_limit::BodyLimit;
+#[cfg(feature = "rustls")]
+pub use client_cert::{ClientCertAuth, PeerCertificate, PeerCertificates, SubjectAltName};
+#[cfg(feature = "compression")]
+pub use compression::Compression;
+pub use connection::{Connection, ConnectionExtensions, ConnectionInfo};
+pub use cors::{Cors, CorsAfter, CorsBefore};
+#[cfg(feature = "compression")]
+pub use decompression::Decompression;
+pub use encoding::Encoding;
+pub use error::{Context, DefaultErrorRenderer, Error, ErrorRenderer, Result};
+pub use extract::*;
+pub use forwarded::{Cidr, ClientInfo, TrustedProxies};
 pub use http::*;
+pub use metrics::{Metrics, MetricsAfter, MetricsBefore, MetricsHandler};
 pub use middleware::*;
+pub use proxy_protocol::{ProxyHeader, ProxyProtocol, ProxyProtocolAcceptor, ProxyProtocolStream};
 pub use remote_addr::*;
+pub use request_id::{IdFormat, RequestId, RequestIdAfter, RequestIdBefore, RequestIds};
+pub use response::ResponseExt;
+pub use router::*;
 pub use service::*;
+pub use shutdown::*;
+pub use state::State;
+pub use static_files::StaticFiles;
+pub use timeout::{Timeout, Timer, TokioTimer};
+#[cfg(feature = "rustls")]
+pub use tls::{ClientAuth, TlsAcceptor, TlsConfig, TlsInfo, TlsReloader, TlsStream};
+#[cfg(unix)]
+pub use unix::{UnixIncoming, UnixPeer};
 
 // Re-export crates
 pub use async_recursion::*;
@@ -0,0 +1,403 @@
+//! The Prometheus metrics module.
+//!
+//! It provides a [`Metrics`] registry which produces a before and after middleware pair
+//! intended to be plugged in via [`Middlewares::link`][`super::Middlewares::link`]
+//! as well as a [`MetricsHandler`] rendering the collected metrics in the
+//! [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/).
+//!
+//! The following metrics are collected:
+//!
+//! - `http_requests_total`: a counter of the handled requests labelled by `method`, `status` class (e.g. `2xx`) and `route`.
+//! - `http_request_duration_seconds`: a histogram of the request durations with the same labels.
+//! - `http_requests_in_flight`: a gauge of the requests in progress labelled by `method`.
+//! - `http_open_connections` and `http_active_requests`: gauges of the connections and requests of a
+//!   [`Service`][`super::Service`], only if its [`Shutdown`] controller was given via [`Metrics::with_shutdown`].
+//!
+//! The `route` label is the [`MatchedPath`] of the request, that is, the pattern of the [`Router`][`super::Router`]
+//! route which matched it (e.g. `/users/:id`), or `unmatched` otherwise, so the label cardinality stays bounded.
+//! For the same reason, the `method` label is one of the standard methods or `other` for extension methods.
+//! Requests failing through the error flow are counted as well with the status of the error (or `500` if it has none).
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{Body, Metrics, Middlewares, Request, Response, Result, Router, Service};
+//!
+//! fn home(_: &mut Request) -> Result<Response> {
+//!     Ok(Response::new(Body::from("¡Hola!")))
+//! }
+//!
+//! let metrics = Metrics::new();
+//!
+//! let mut router = Router::new();
+//! router.get("/", home).get("/metrics", metrics.handler());
+//!
+//! let mut middlewares = Middlewares::new(router);
+//! middlewares.link(metrics.build());
+//!
+//! let service = Service::new(middlewares);
+//! // Include the connection gauges of the service
+//! let metrics = metrics.with_shutdown(service.shutdown());
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderValue};
+use hyper::{Method, StatusCode};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+use crate::middleware::{AfterMiddleware, BeforeMiddleware, Handler};
+use crate::router::MatchedPath;
+use crate::shutdown::Shutdown;
+use crate::{Body, Error, Request, Response, Result};
+
+/// The default histogram buckets in seconds.
+const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The `route` label of requests which matched no route.
+const UNMATCHED: &str = "unmatched";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Labels {
+    method: &'static str,
+    status: &'static str,
+    route: String,
+}
+
+#[derive(Debug)]
+struct Series {
+    count: u64,
+    sum: f64,
+    buckets: Vec<u64>,
+}
+
+#[derive(Debug)]
+struct Registry {
+    buckets: Vec<f64>,
+    series: Mutex<BTreeMap<Labels, Series>>,
+    in_flight: Mutex<BTreeMap<&'static str, Arc<AtomicI64>>>,
+    shutdown: RwLock<Option<Shutdown>>,
+}
+
+/// The metrics registry which produces a [`MetricsBefore`] and [`MetricsAfter`] middleware pair
+/// and a [`MetricsHandler`].
+///
+/// It can be cheaply cloned, all clones share the same metrics.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Arc<Registry>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Create a new metrics registry with the default histogram buckets
+    /// (from 5 milliseconds to 10 seconds).
+    pub fn new() -> Self {
+        Self::with_buckets(DEFAULT_BUCKETS.to_vec())
+    }
+
+    /// Create a new metrics registry with the given histogram buckets in seconds.
+    pub fn with_buckets<I>(buckets: I) -> Self
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        let mut buckets: Vec<f64> = buckets.into_iter().filter(|b| b.is_finite()).collect();
+        buckets.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        buckets.dedup();
+        Self {
+            registry: Arc::new(Registry {
+                buckets,
+                series: Mutex::new(BTreeMap::new()),
+                in_flight: Mutex::new(BTreeMap::new()),
+                shutdown: RwLock::new(None),
+            }),
+        }
+    }
+
+    /// Include the connection and request gauges of the [`Service`][`super::Service`] owning the given [`Shutdown`] controller.
+    pub fn with_shutdown(self, shutdown: Shutdown) -> Self {
+        if let Ok(mut current) = self.registry.shutdown.write() {
+            *current = Some(shutdown);
+        }
+        self
+    }
+
+    /// Build the middleware pair to be plugged in via [`Middlewares::link`][`super::Middlewares::link`].
+    pub fn build(&self) -> (MetricsBefore, MetricsAfter) {
+        (
+            MetricsBefore {
+                registry: self.registry.clone(),
+            },
+            MetricsAfter {
+                registry: self.registry.clone(),
+            },
+        )
+    }
+
+    /// Returns a [`Handler`] which renders the metrics in the Prometheus text format.
+    pub fn handler(&self) -> MetricsHandler {
+        MetricsHandler {
+            registry: self.registry.clone(),
+        }
+    }
+
+    /// Render the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        self.registry.render()
+    }
+}
+
+impl Registry {
+    fn in_flight(&self, method: &Method) -> Arc<AtomicI64> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        in_flight
+            .entry(method_label(method))
+            .or_insert_with(|| Arc::new(AtomicI64::new(0)))
+            .clone()
+    }
+
+    fn observe(&self, req: &mut Request, status: StatusCode) {
+        let seconds = match req.extensions_mut().remove::<InFlight>() {
+            Some(in_flight) => in_flight.start.elapsed().as_secs_f64(),
+            None => return,
+        };
+        let labels = Labels {
+            method: method_label(req.method()),
+            status: status_class(status),
+            route: req
+                .extensions()
+                .get::<MatchedPath>()
+                .map(|path| path.0.clone())
+                .unwrap_or_else(|| UNMATCHED.to_owned()),
+        };
+
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let series = series.entry(labels).or_insert_with(|| Series {
+            count: 0,
+            sum: 0.0,
+            buckets: vec![0; self.buckets.len()],
+        });
+        series.count += 1;
+        series.sum += seconds;
+        for (i, bound) in self.buckets.iter().enumerate() {
+            if seconds <= *bound {
+                series.buckets[i] += 1;
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        out.push_str("# HELP http_requests_total Total number of HTTP requests.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for (labels, series) in series.iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{{}}} {}",
+                labels.render(),
+                series.count
+            );
+        }
+
+        out.push_str("# HELP http_request_duration_seconds HTTP request duration in seconds.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (labels, series) in series.iter() {
+            let labels = labels.render();
+            for (bound, count) in self.buckets.iter().zip(&series.buckets) {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, series.count
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_sum{{{}}} {}",
+                labels, series.sum
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_count{{{}}} {}",
+                labels, series.count
+            );
+        }
+        drop(series);
+
+        let in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        out.push_str("# HELP http_requests_in_flight Number of HTTP requests in progress.\n");
+        out.push_str("# TYPE http_requests_in_flight gauge\n");
+        for (method, value) in in_flight.iter() {
+            let _ = writeln!(
+                out,
+                "http_requests_in_flight{{method=\"{}\"}} {}",
+                method,
+                value.load(Ordering::SeqCst)
+            );
+        }
+        drop(in_flight);
+
+        let shutdown = self.shutdown.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(shutdown) = shutdown.as_ref() {
+            out.push_str("# HELP http_open_connections Number of open HTTP connections.\n");
+            out.push_str("# TYPE http_open_connections gauge\n");
+            let _ = writeln!(
+                out,
+                "http_open_connections {}",
+                shutdown.active_connections()
+            );
+            out.push_str(
+                "# HELP http_active_requests Number of HTTP requests in progress on the service.\n",
+            );
+            out.push_str("# TYPE http_active_requests gauge\n");
+            let _ = writeln!(out, "http_active_requests {}", shutdown.active_requests());
+        }
+
+        out
+    }
+}
+
+impl Labels {
+    fn render(&self) -> String {
+        format!(
+            "method=\"{}\",status=\"{}\",route=\"{}\"",
+            self.method,
+            self.status,
+            escape_label(&self.route)
+        )
+    }
+}
+
+/// Keeps a request counted as in flight while alive.
+///
+/// It is stored in the request extensions so requests which are dropped
+/// (e.g. cancelled) are not counted forever.
+struct InFlight {
+    start: Instant,
+    gauge: Arc<AtomicI64>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.gauge.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The [`BeforeMiddleware`] part of [`Metrics`] which tracks the requests in flight.
+#[derive(Debug, Clone)]
+pub struct MetricsBefore {
+    registry: Arc<Registry>,
+}
+
+impl MetricsBefore {
+    fn start(&self, req: &mut Request) {
+        let gauge = self.registry.in_flight(req.method());
+        gauge.fetch_add(1, Ordering::SeqCst);
+        req.extensions_mut().insert(InFlight {
+            start: Instant::now(),
+            gauge,
+        });
+    }
+}
+
+#[async_trait]
+impl BeforeMiddleware for MetricsBefore {
+    async fn before(&self, req: &mut Request) -> Result {
+        self.start(req);
+        Ok(())
+    }
+
+    async fn catch(&self, req: &mut Request, err: Error) -> Result {
+        self.start(req);
+        Err(err)
+    }
+}
+
+/// The [`AfterMiddleware`] part of [`Metrics`] which records the request counters and durations.
+#[derive(Debug, Clone)]
+pub struct MetricsAfter {
+    registry: Arc<Registry>,
+}
+
+#[async_trait]
+impl AfterMiddleware for MetricsAfter {
+    async fn after(&self, req: &mut Request, res: Response) -> Result<Response> {
+        self.registry.observe(req, res.status());
+        Ok(res)
+    }
+
+    async fn catch(&self, req: &mut Request, err: Error) -> Result<Response> {
+        let status = err.status().unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        self.registry.observe(req, status);
+        Err(err)
+    }
+}
+
+/// A [`Handler`] which renders the metrics of a [`Metrics`] registry in the Prometheus text format.
+#[derive(Debug, Clone)]
+pub struct MetricsHandler {
+    registry: Arc<Registry>,
+}
+
+#[async_trait]
+impl Handler for MetricsHandler {
+    async fn handle(&self, _: &mut Request) -> Result<Response> {
+        Ok(hyper::Response::builder()
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+            )
+            .body(Body::from(self.registry.render()))?)
+    }
+}
+
+// Returns the label of a request method, extension methods share the `other` label.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => "other",
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
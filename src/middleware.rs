@@ -90,10 +90,10 @@
 //!     // Plug in the custom middleware(s)
 //!     middlewares.link_before(RequestLoggingMiddleware {});
 //!
-//!     let addr = ([127, 0, 0, 1], 8080).into();
+//!     let addr = ([127, 0, 0, 1], 0).into();
 //!     let service = Service::new(middlewares);
 //!     let server = Server::bind(&addr).serve(service);
-//!     println!("Listening on http://{}", addr);
+//!     println!("Listening on http://{}", server.local_addr());
 //!
 //!     // server.await?;
 //!
@@ -125,6 +125,15 @@
 //! `Request` and `Response`. After all `AfterMiddleware` have been fired, the
 //! response is written back to the client.
 //!
+//! A `BeforeMiddleware` can also short-circuit the flow by responding early
+//! via [`BeforeMiddleware::intercept`] (e.g. a cache hit, a CORS preflight or a redirect).
+//! In that case the remaining `BeforeMiddleware` and the `Handler` are skipped
+//! and the `Response` goes straight into the `AfterMiddleware`:
+//!
+//! ```plain
+//! [b] -> [b] -> [a] -> [a] -> [a] -> [a]
+//! ```
+//!
 //! Iron's error handling system is pragmatic and focuses on tracking two pieces
 //! of information for error receivers (other middleware):
 //!
@@ -157,11 +166,20 @@
 //! during the error flow. Anything that *must* be done to each `Request` or
 //! `Response` should be run during both the normal and error flow by
 //! implementing the `catch` method to also do the necessary action.
+//!
+//! # Tracing
+//!
+//! When the `tracing` feature is enabled, the [`Service`][`crate::Service`] creates a `request` span per request
+//! and every `before`, `handle`, `after` and `catch` call of a `Middlewares` chain runs inside of a child
+//! `middleware` span. These spans record the phase, the middleware name (see [`BeforeMiddleware::name`])
+//! and the outcome of the call, including the error and its status code if it failed.
 
 use async_recursion::async_recursion;
 use async_trait::async_trait;
 use std::sync::Arc;
 
+use crate::state::States;
+use crate::trace::{traced, Phase};
 use crate::{Error, Request, Response, Result};
 
 #[async_trait]
@@ -169,6 +187,13 @@ use crate::{Error, Request, Response, Result};
 pub trait Handler: Send + Sync + 'static {
     /// Produce a `Response` from a Request, with the possibility of error.
     async fn handle(&self, req: &mut Request) -> Result<Response>;
+
+    /// Returns the name of this `Handler` used for diagnostics (e.g. `tracing` spans).
+    ///
+    /// Defaults to its type name.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 
 #[async_trait]
@@ -186,6 +211,10 @@ impl Handler for Box<dyn Handler> {
     async fn handle(&self, req: &mut Request) -> Result<Response> {
         (**self).handle(req).await
     }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
 }
 
 #[async_trait]
@@ -197,14 +226,24 @@ impl Handler for Box<dyn Handler> {
 ///
 /// `BeforeMiddleware` only have access to the Request, if you need to modify or read
 /// a Response, you will need `AfterMiddleware`. Middleware which wishes to send an
-/// early response that is not an error cannot be `BeforeMiddleware`, but should
-/// instead be `AroundMiddleware`.
+/// early response that is not an error should implement the `intercept` method instead of `before`.
 pub trait BeforeMiddleware: Send + Sync + 'static {
     /// Do whatever work this middleware should do with a `Request` object.
     async fn before(&self, _: &mut Request) -> Result<()> {
         Ok(())
     }
 
+    /// Do whatever work this middleware should do with a `Request` object
+    /// with the possibility of responding early.
+    ///
+    /// Returning [`BeforeOutcome::Respond`] will skip the remaining `BeforeMiddleware`
+    /// and the `Handler`, passing the `Response` directly to the `AfterMiddleware`.
+    ///
+    /// By default, it calls `before` and continues the normal flow.
+    async fn intercept(&self, req: &mut Request) -> Result<BeforeOutcome> {
+        self.before(req).await.map(|_| BeforeOutcome::Continue)
+    }
+
     /// Respond to an error thrown by a previous `BeforeMiddleware`.
     ///
     /// Returning a `Ok` will cause the request to resume the normal flow at the
@@ -213,6 +252,61 @@ pub trait BeforeMiddleware: Send + Sync + 'static {
     async fn catch(&self, _: &mut Request, err: Error) -> Result<()> {
         Err(err)
     }
+
+    /// Returns the name of this middleware used for diagnostics (e.g. `tracing` spans).
+    ///
+    /// Defaults to its type name.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// The outcome of a [`BeforeMiddleware::intercept`] call.
+///
+/// ## Example
+///
+/// ```rust
+/// use hyper::{header, StatusCode};
+/// use hyper_middleware::{
+///     async_trait, BeforeMiddleware, BeforeOutcome, Body, Handler, Middlewares, Request,
+///     Response, Result,
+/// };
+///
+/// struct RedirectMiddleware {}
+///
+/// #[async_trait]
+/// impl BeforeMiddleware for RedirectMiddleware {
+///     async fn intercept(&self, req: &mut Request) -> Result<BeforeOutcome> {
+///         if req.uri().path() != "/old" {
+///             return Ok(BeforeOutcome::Continue);
+///         }
+///         let mut res = Response::new(Body::empty());
+///         *res.status_mut() = StatusCode::MOVED_PERMANENTLY;
+///         res.headers_mut()
+///             .insert(header::LOCATION, header::HeaderValue::from_static("/new"));
+///         Ok(BeforeOutcome::Respond(res))
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "multi_thread")]
+/// async fn main() -> Result {
+///     let handler = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::empty())) };
+///     let mut middlewares = Middlewares::new(handler);
+///     middlewares.link_before(RedirectMiddleware {});
+///
+///     let mut req = Request::builder().uri("/old").body(Body::empty()).unwrap();
+///     let res = middlewares.handle(&mut req).await?;
+///     assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub enum BeforeOutcome {
+    /// Continue the normal flow at the next `BeforeMiddleware` or the `Handler`.
+    Continue,
+    /// Skip the remaining `BeforeMiddleware` and the `Handler` and respond with the given `Response`.
+    Respond(Response),
 }
 
 #[async_trait]
@@ -238,6 +332,13 @@ pub trait AfterMiddleware: Send + Sync + 'static {
     async fn catch(&self, _: &mut Request, err: Error) -> Result<Response> {
         Err(err)
     }
+
+    /// Returns the name of this middleware used for diagnostics (e.g. `tracing` spans).
+    ///
+    /// Defaults to its type name.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 
 #[async_trait(?Send)]
@@ -265,6 +366,7 @@ pub trait AroundMiddleware {
 pub struct Middlewares {
     befores: Vec<Box<dyn BeforeMiddleware>>,
     afters: Vec<Box<dyn AfterMiddleware>>,
+    states: States,
 
     // Internal invariant: this is always Some
     handler: Option<Box<dyn Handler>>,
@@ -279,6 +381,7 @@ impl Middlewares {
         Self {
             befores: vec![],
             afters: vec![],
+            states: States::default(),
             handler: Some(Box::new(handler) as Box<dyn Handler>),
         }
     }
@@ -322,6 +425,18 @@ impl Middlewares {
         self
     }
 
+    /// Add a typed state value which will be available to every middleware and the `Handler`
+    /// of this `Middlewares` chain via the request extensions as [`State<T>`][`crate::State`].
+    ///
+    /// A value of the same type added to a nested `Middlewares` chain takes precedence.
+    pub fn add_state<T>(&mut self, value: T) -> &mut Middlewares
+    where
+        T: Send + Sync + 'static,
+    {
+        self.states.insert(value);
+        self
+    }
+
     /// Apply an `AroundMiddleware` to the `Handler` in this `Middlewares` chain.
     pub async fn link_around<A>(&mut self, around: A) -> &mut Middlewares
     where
@@ -337,6 +452,8 @@ impl Middlewares {
 #[async_trait]
 impl Handler for Middlewares {
     async fn handle(&self, req: &mut Request) -> Result<Response> {
+        self.states.apply(req);
+
         // Kick off at befores, which will continue into handler
         // then afters.
         self.continue_from_before(req, 0).await
@@ -362,7 +479,7 @@ impl Middlewares {
         }
 
         for (i, before) in self.befores[index..].iter().enumerate() {
-            err = match before.catch(req, err).await {
+            err = match traced(Phase::Catch, before.name(), before.catch(req, err)).await {
                 Err(err) => err,
                 Ok(()) => return self.continue_from_before(req, index + i + 1).await,
             };
@@ -381,8 +498,11 @@ impl Middlewares {
         }
 
         for (i, before) in self.befores[index..].iter().enumerate() {
-            match before.before(req).await {
-                Ok(()) => {}
+            match traced(Phase::Before, before.name(), before.intercept(req)).await {
+                Ok(BeforeOutcome::Continue) => {}
+                Ok(BeforeOutcome::Respond(res)) => {
+                    return self.continue_from_after(req, 0, res).await
+                }
                 Err(err) => return self.fail_from_before(req, index + i + 1, err).await,
             }
         }
@@ -415,7 +535,7 @@ impl Middlewares {
         }
 
         for (i, after) in self.afters[index..].iter().enumerate() {
-            err = match after.catch(req, err).await {
+            err = match traced(Phase::Catch, after.name(), after.catch(req, err)).await {
                 Err(err) => err,
                 Ok(res) => return self.continue_from_after(req, index + i + 1, res).await,
             }
@@ -428,7 +548,8 @@ impl Middlewares {
     // Enter the normal flow at the handler.
     async fn continue_from_handler(&self, req: &mut Request) -> Result<Response> {
         // unwrap is safe because it's always Some
-        match self.handler.as_ref().unwrap().handle(req).await {
+        let handler = self.handler.as_ref().unwrap();
+        match traced(Phase::Handler, handler.name(), handler.handle(req)).await {
             Ok(res) => self.continue_from_after(req, 0, res).await,
             Err(err) => self.fail_from_handler(req, err).await,
         }
@@ -449,7 +570,7 @@ impl Middlewares {
         }
 
         for (i, after) in self.afters[index..].iter().enumerate() {
-            res = match after.after(req, res).await {
+            res = match traced(Phase::After, after.name(), after.after(req, res)).await {
                 Ok(res) => res,
                 Err(err) => return self.fail_from_after(req, index + i + 1, err).await,
             }
@@ -476,9 +597,17 @@ impl BeforeMiddleware for Box<dyn BeforeMiddleware> {
         (**self).before(req).await
     }
 
+    async fn intercept(&self, req: &mut Request) -> Result<BeforeOutcome> {
+        (**self).intercept(req).await
+    }
+
     async fn catch(&self, req: &mut Request, err: Error) -> Result<()> {
         (**self).catch(req, err).await
     }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
 }
 
 #[async_trait]
@@ -490,9 +619,17 @@ where
         (**self).before(req).await
     }
 
+    async fn intercept(&self, req: &mut Request) -> Result<BeforeOutcome> {
+        (**self).intercept(req).await
+    }
+
     async fn catch(&self, req: &mut Request, err: Error) -> Result<()> {
         (**self).catch(req, err).await
     }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
 }
 
 #[async_trait]
@@ -514,6 +651,10 @@ impl AfterMiddleware for Box<dyn AfterMiddleware> {
     async fn catch(&self, req: &mut Request, err: Error) -> Result<Response> {
         (**self).catch(req, err).await
     }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
 }
 
 #[async_trait]
@@ -528,6 +669,10 @@ where
     async fn catch(&self, req: &mut Request, err: Error) -> Result<Response> {
         (**self).catch(req, err).await
     }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
 }
 
 #[async_trait(?Send)]
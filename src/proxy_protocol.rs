@@ -0,0 +1,465 @@
+//! The PROXY protocol module.
+//!
+//! It provides support for the [HAProxy PROXY protocol](https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt)
+//! (versions 1 and 2) used by TCP load balancers to pass the original client address to the backend.
+//!
+//! - [`ProxyProtocol`] configures how the header is read (strict mode and read timeout).
+//! - [`ProxyProtocolAcceptor`] wraps a Hyper [`Accept`] (e.g. [`AddrIncoming`][`hyper::server::conn::AddrIncoming`])
+//!   and reads the header of every accepted connection before HTTP begins.
+//! - [`ProxyProtocolStream`] wraps a connection stream and implements [`RemoteAddr`]
+//!   so the [`Service`][`super::Service`] reports the original client address.
+//!   The [`ProxyHeader`] is also inserted into the request extensions.
+//!
+//! In strict mode, connections lacking a valid header are rejected (closed).
+//! Otherwise they are served as-is with the address of the peer.
+//! Note that the non-strict mode allows any client to send a header and spoof its address,
+//! so it should only be used when all the connections come from trusted load balancers.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper::server::conn::AddrIncoming;
+//! use hyper::Server;
+//! use hyper_middleware::{Body, ProxyProtocol, Request, Response, Result, Service};
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() -> Result {
+//!     let handler = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::from("¡Hola!"))) };
+//!
+//!     let addr = ([127, 0, 0, 1], 0).into();
+//!     let incoming = AddrIncoming::bind(&addr)?;
+//!     let local_addr = incoming.local_addr();
+//!     let acceptor = ProxyProtocol::new().strict(true).acceptor(incoming);
+//!
+//!     let server = Server::builder(acceptor).serve(Service::new(handler));
+//!
+//!     println!("Listening on http://{}", local_addr);
+//!
+//!     // server.await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use hyper::server::accept::Accept;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+use crate::connection::{ConnectionExtensions, ConnectionInfo};
+use crate::future::Handshakes;
+use crate::remote_addr::RemoteAddr;
+
+/// The signature of a version 2 header.
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The prefix of a version 1 header.
+const V1_PREFIX: &[u8; 6] = b"PROXY ";
+
+/// The maximum length of a version 1 header including the trailing CRLF.
+const V1_MAX_LENGTH: usize = 107;
+
+/// The PROXY protocol header of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// The protocol version (`1` or `2`).
+    pub version: u8,
+    /// The original source (client) address, if provided by the proxy.
+    pub source: Option<SocketAddr>,
+    /// The original destination address, if provided by the proxy.
+    pub destination: Option<SocketAddr>,
+}
+
+/// The PROXY protocol configuration.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct ProxyProtocol {
+    strict: bool,
+    timeout: Duration,
+}
+
+impl Default for ProxyProtocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProxyProtocol {
+    /// Create a new non-strict configuration with a read timeout of 5 seconds.
+    pub fn new() -> Self {
+        Self {
+            strict: false,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Reject the connections lacking a PROXY protocol header.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the maximum time to wait for the header of a connection.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Wrap a Hyper [`Accept`] so the header of every accepted connection is read.
+    pub fn acceptor<I>(self, incoming: I) -> ProxyProtocolAcceptor<I>
+    where
+        I: Accept,
+    {
+        ProxyProtocolAcceptor {
+            config: self,
+            handshakes: Handshakes::new(incoming),
+        }
+    }
+
+    /// Read the header of a single connection stream.
+    ///
+    /// It's useful for custom acceptors, for example to read the header before a TLS handshake.
+    pub async fn accept<S>(&self, stream: S) -> io::Result<ProxyProtocolStream<S>>
+    where
+        S: AsyncRead + Unpin,
+    {
+        match tokio::time::timeout(self.timeout, read_header(stream, self.strict)).await {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out reading the PROXY protocol header",
+            )),
+        }
+    }
+}
+
+/// A connection stream whose PROXY protocol header was read.
+///
+/// Its [`RemoteAddr`] is the source address of the header or the peer address of the inner stream otherwise.
+#[derive(Debug)]
+pub struct ProxyProtocolStream<S> {
+    inner: S,
+    header: Option<ProxyHeader>,
+    // Bytes read past the header which are replayed first
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl<S> ProxyProtocolStream<S> {
+    /// Returns the PROXY protocol header of the connection, if any.
+    pub fn header(&self) -> Option<&ProxyHeader> {
+        self.header.as_ref()
+    }
+
+    /// Returns a reference to the inner stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+}
+
+impl<S> RemoteAddr for ProxyProtocolStream<S>
+where
+    S: RemoteAddr,
+{
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.header
+            .and_then(|header| header.source)
+            .or_else(|| self.inner.remote_addr())
+    }
+}
+
+impl<S> ConnectionInfo for ProxyProtocolStream<S>
+where
+    S: ConnectionInfo,
+{
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn connection_id(&self) -> Option<u64> {
+        self.inner.connection_id()
+    }
+
+    fn accepted_at(&self) -> Option<SystemTime> {
+        self.inner.accepted_at()
+    }
+
+    fn extensions(&self) -> ConnectionExtensions {
+        let mut extensions = self.inner.extensions();
+        if let Some(header) = self.header {
+            extensions.insert(header);
+        }
+        extensions
+    }
+}
+
+impl<S> AsyncRead for ProxyProtocolStream<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.position < this.buffer.len() {
+            let remaining = &this.buffer[this.position..];
+            let n = remaining.len().min(buf.remaining());
+            buf.put_slice(&remaining[..n]);
+            this.position += n;
+            if this.position == this.buffer.len() {
+                this.buffer = Vec::new();
+                this.position = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S> AsyncWrite for ProxyProtocolStream<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// A Hyper [`Accept`] which reads the PROXY protocol header of the accepted connections.
+///
+/// Headers are read concurrently so slow connections don't hold back the other ones.
+/// Connections whose header is invalid, missing in strict mode or not received in time are closed.
+pub struct ProxyProtocolAcceptor<I>
+where
+    I: Accept,
+{
+    config: ProxyProtocol,
+    handshakes: Handshakes<I, ProxyProtocolStream<I::Conn>>,
+}
+
+impl<I> ProxyProtocolAcceptor<I>
+where
+    I: Accept,
+{
+    /// Returns a reference to the inner acceptor.
+    pub fn get_ref(&self) -> &I {
+        self.handshakes.get_ref()
+    }
+}
+
+impl<I> Accept for ProxyProtocolAcceptor<I>
+where
+    I: Accept + Unpin,
+    I::Conn: AsyncRead + Send + Unpin + 'static,
+{
+    type Conn = ProxyProtocolStream<I::Conn>;
+    type Error = I::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let this = self.get_mut();
+        let config = &this.config;
+        this.handshakes
+            .poll_accept(cx, "PROXY protocol header read", |conn| {
+                let config = config.clone();
+                Box::pin(async move { config.accept(conn).await })
+            })
+    }
+}
+
+async fn read_header<S>(mut stream: S, strict: bool) -> io::Result<ProxyProtocolStream<S>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buffer = Vec::with_capacity(256);
+    loop {
+        if let Some((header, len)) = parse(&buffer)? {
+            buffer.drain(..len);
+            return Ok(ProxyProtocolStream {
+                inner: stream,
+                header: Some(header),
+                buffer,
+                position: 0,
+            });
+        }
+
+        if !is_prefix(&buffer) {
+            if strict {
+                return Err(invalid("missing PROXY protocol header"));
+            }
+            return Ok(ProxyProtocolStream {
+                inner: stream,
+                header: None,
+                buffer,
+                position: 0,
+            });
+        }
+
+        let mut chunk = [0u8; 256];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before the PROXY protocol header",
+            ));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+}
+
+// Returns `true` if the buffer may still become a header once more bytes are read.
+fn is_prefix(buf: &[u8]) -> bool {
+    let starts = |prefix: &[u8]| {
+        let n = buf.len().min(prefix.len());
+        buf[..n] == prefix[..n]
+    };
+    (starts(V1_PREFIX) && buf.len() < V1_MAX_LENGTH) || starts(V2_SIGNATURE)
+}
+
+// Parse a complete header, returns `None` if more bytes are needed.
+fn parse(buf: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    if buf.starts_with(V1_PREFIX) {
+        return parse_v1(buf);
+    }
+    if buf.starts_with(V2_SIGNATURE) {
+        return parse_v2(buf);
+    }
+    Ok(None)
+}
+
+fn parse_v1(buf: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    let end = match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(end) if end + 2 <= V1_MAX_LENGTH => end,
+        Some(_) => return Err(invalid("PROXY protocol v1 header too long")),
+        None if buf.len() >= V1_MAX_LENGTH => {
+            return Err(invalid("PROXY protocol v1 header too long"))
+        }
+        None => return Ok(None),
+    };
+    let line = std::str::from_utf8(&buf[V1_PREFIX.len()..end])
+        .map_err(|_| invalid("invalid PROXY protocol v1 header"))?;
+
+    let parts: Vec<&str> = line.split(' ').collect();
+    let (source, destination) = match parts.as_slice() {
+        ["UNKNOWN", ..] => (None, None),
+        [family @ ("TCP4" | "TCP6"), src, dst, sport, dport] => {
+            let ip = |s: &str| -> io::Result<IpAddr> {
+                let ip = match *family {
+                    "TCP4" => s.parse::<Ipv4Addr>().map(IpAddr::V4),
+                    _ => s.parse::<Ipv6Addr>().map(IpAddr::V6),
+                };
+                ip.map_err(|_| invalid("invalid PROXY protocol v1 address"))
+            };
+            let port = |s: &str| -> io::Result<u16> {
+                s.parse::<u16>()
+                    .map_err(|_| invalid("invalid PROXY protocol v1 port"))
+            };
+            (
+                Some(SocketAddr::new(ip(src)?, port(sport)?)),
+                Some(SocketAddr::new(ip(dst)?, port(dport)?)),
+            )
+        }
+        _ => return Err(invalid("invalid PROXY protocol v1 header")),
+    };
+
+    let header = ProxyHeader {
+        version: 1,
+        source,
+        destination,
+    };
+    Ok(Some((header, end + 2)))
+}
+
+fn parse_v2(buf: &[u8]) -> io::Result<Option<(ProxyHeader, usize)>> {
+    if buf.len() < 16 {
+        return Ok(None);
+    }
+    let version = buf[12] >> 4;
+    let command = buf[12] & 0x0f;
+    let family = buf[13];
+    let len = usize::from(u16::from_be_bytes([buf[14], buf[15]]));
+    if version != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    if buf.len() < 16 + len {
+        return Ok(None);
+    }
+    let addrs = &buf[16..16 + len];
+
+    let (source, destination) = match (command, family >> 4) {
+        // LOCAL connections (e.g. health checks) keep the peer address
+        (0x0, _) => (None, None),
+        (0x1, 0x1) if addrs.len() >= 12 => {
+            let src: [u8; 4] = addrs[0..4].try_into().unwrap_or_default();
+            let dst: [u8; 4] = addrs[4..8].try_into().unwrap_or_default();
+            (
+                Some(SocketAddr::new(src.into(), port(&addrs[8..10]))),
+                Some(SocketAddr::new(dst.into(), port(&addrs[10..12]))),
+            )
+        }
+        (0x1, 0x2) if addrs.len() >= 36 => {
+            let src: [u8; 16] = addrs[0..16].try_into().unwrap_or_default();
+            let dst: [u8; 16] = addrs[16..32].try_into().unwrap_or_default();
+            (
+                Some(SocketAddr::new(src.into(), port(&addrs[32..34]))),
+                Some(SocketAddr::new(dst.into(), port(&addrs[34..36]))),
+            )
+        }
+        // Unspecified and Unix socket families carry no IP addresses
+        (0x1, 0x0 | 0x3) => (None, None),
+        (0x1, _) => return Err(invalid("invalid PROXY protocol v2 addresses")),
+        _ => return Err(invalid("invalid PROXY protocol v2 command")),
+    };
+
+    let header = ProxyHeader {
+        version: 2,
+        source,
+        destination,
+    };
+    Ok(Some((header, 16 + len)))
+}
+
+fn port(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
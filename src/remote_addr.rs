@@ -6,6 +6,29 @@ use std::net::SocketAddr;
 /// Defines a method to get the remote (peer) address of a connection.
 ///
 /// This trait might be needed to be implemented by for example custom TLS implementations.
+/// Built-in TLS support is provided by the `rustls` feature.
+/// See also [`ConnectionInfo`][`crate::ConnectionInfo`] for the rest of the connection information.
+///
+/// The [`Service`][`crate::Service`] requires connection types to implement both traits.
+/// Since every `ConnectionInfo` method is provided, a type which only implemented `RemoteAddr`
+/// just needs an empty implementation:
+///
+/// ```rust
+/// use hyper_middleware::{ConnectionInfo, RemoteAddr};
+/// use std::net::SocketAddr;
+///
+/// struct MyStream {
+///     peer: SocketAddr,
+/// }
+///
+/// impl RemoteAddr for MyStream {
+///     fn remote_addr(&self) -> Option<SocketAddr> {
+///         Some(self.peer)
+///     }
+/// }
+///
+/// impl ConnectionInfo for MyStream {}
+/// ```
 pub trait RemoteAddr {
     /// Returns the remote (peer) address of this connection.
     fn remote_addr(&self) -> Option<SocketAddr>;
@@ -0,0 +1,286 @@
+//! The request ID module.
+//!
+//! It provides a configurable [`RequestIds`] component made of a before and after middleware pair
+//! intended to be plugged in via [`Middlewares::link`][`super::Middlewares::link`].
+//!
+//! - The [`RequestIdBefore`] middleware reads the request ID from the `X-Request-Id` header (or a custom one)
+//!   or generates a new one if missing or invalid, and stores it in the request extensions as [`RequestId`].
+//! - The [`RequestIdAfter`] middleware echoes the request ID in the same response header.
+//!
+//! Errors flowing through the `catch` methods of both middleware get the request ID attached
+//! (see [`Error::request_id`][`crate::Error::request_id`]) as well as the response header,
+//! so error logs and responses can be correlated.
+//!
+//! New IDs are generated as [UUID](https://www.rfc-editor.org/rfc/rfc4122) v4 by default,
+//! [ULID](https://github.com/ulid/spec) or by a custom generator. See [`IdFormat`].
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{Body, IdFormat, Middlewares, Request, RequestId, RequestIds, Response, Result};
+//!
+//! let handler = |req: &mut Request| -> Result<Response> {
+//!     let id = req.extensions().get::<RequestId>().unwrap();
+//!     Ok(Response::new(Body::from(format!("Request: {}", id))))
+//! };
+//!
+//! let request_ids = RequestIds::new()
+//!     .header("x-correlation-id")
+//!     .format(IdFormat::Ulid);
+//!
+//! let mut middlewares = Middlewares::new(handler);
+//! middlewares.link(request_ids.build());
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{HeaderName, HeaderValue};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::middleware::{AfterMiddleware, BeforeMiddleware};
+use crate::{Error, Request, Response, Result};
+
+/// The maximum length of a request ID accepted from a request header.
+const MAX_LENGTH: usize = 128;
+
+type Generator = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// The ID of a request stored in the request extensions by [`RequestIdBefore`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub(crate) fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// Returns the request ID as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The format of the generated request IDs.
+#[derive(Clone)]
+pub enum IdFormat {
+    /// A random UUID v4, e.g. `936da01f-9abd-4d9d-80c7-02af85c822a8`.
+    Uuid,
+    /// A ULID, e.g. `01ARZ3NDEKTSV4RRFFQ69G5FAV`, which sorts by creation time.
+    Ulid,
+    /// A custom generator.
+    Custom(Generator),
+}
+
+impl fmt::Debug for IdFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdFormat::Uuid => f.write_str("Uuid"),
+            IdFormat::Ulid => f.write_str("Ulid"),
+            IdFormat::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl IdFormat {
+    /// Create a custom format from a generator function.
+    pub fn custom<F>(generator: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        IdFormat::Custom(Arc::new(generator))
+    }
+
+    fn generate(&self) -> String {
+        match self {
+            IdFormat::Uuid => uuid_v4(),
+            IdFormat::Ulid => ulid(),
+            IdFormat::Custom(generator) => generator(),
+        }
+    }
+}
+
+/// The request ID configuration which produces a [`RequestIdBefore`] and [`RequestIdAfter`] middleware pair.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct RequestIds {
+    header: HeaderName,
+    format: IdFormat,
+}
+
+impl Default for RequestIds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestIds {
+    /// Create a new configuration which uses the `X-Request-Id` header and generates UUID v4 IDs.
+    pub fn new() -> Self {
+        Self {
+            header: HeaderName::from_static("x-request-id"),
+            format: IdFormat::Uuid,
+        }
+    }
+
+    /// Set the header the request ID is read from and echoed in.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the header name is not valid.
+    pub fn header(mut self, header: &str) -> Self {
+        self.header = HeaderName::from_bytes(header.as_bytes())
+            .unwrap_or_else(|_| panic!("invalid request ID header name `{}`", header));
+        self
+    }
+
+    /// Set the format of the generated request IDs.
+    pub fn format(mut self, format: IdFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Build the middleware pair to be plugged in via [`Middlewares::link`][`super::Middlewares::link`].
+    pub fn build(self) -> (RequestIdBefore, RequestIdAfter) {
+        let config = Arc::new(self);
+        (
+            RequestIdBefore {
+                config: config.clone(),
+            },
+            RequestIdAfter { config },
+        )
+    }
+
+    // Returns the ID of the request, reading or generating it if not done yet.
+    fn ensure(&self, req: &mut Request) -> RequestId {
+        if let Some(id) = req.extensions().get::<RequestId>() {
+            return id.clone();
+        }
+        let id = req
+            .headers()
+            .get(&self.header)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| is_valid(v))
+            .map(str::to_owned)
+            .unwrap_or_else(|| self.format.generate());
+        let id = RequestId(id);
+        req.extensions_mut().insert(id.clone());
+        id
+    }
+
+    fn attach(&self, id: &RequestId, err: Error) -> Error {
+        let err = err.with_request_id(id.as_str());
+        match HeaderValue::from_str(id.as_str()) {
+            Ok(value) => err.with_header(self.header.clone(), value),
+            Err(_) => err,
+        }
+    }
+}
+
+/// The [`BeforeMiddleware`] part of [`RequestIds`] which reads or generates the request ID.
+#[derive(Debug, Clone)]
+pub struct RequestIdBefore {
+    config: Arc<RequestIds>,
+}
+
+#[async_trait]
+impl BeforeMiddleware for RequestIdBefore {
+    async fn before(&self, req: &mut Request) -> Result {
+        self.config.ensure(req);
+        Ok(())
+    }
+
+    async fn catch(&self, req: &mut Request, err: Error) -> Result {
+        let id = self.config.ensure(req);
+        Err(self.config.attach(&id, err))
+    }
+}
+
+/// The [`AfterMiddleware`] part of [`RequestIds`] which echoes the request ID in the response.
+#[derive(Debug, Clone)]
+pub struct RequestIdAfter {
+    config: Arc<RequestIds>,
+}
+
+#[async_trait]
+impl AfterMiddleware for RequestIdAfter {
+    async fn after(&self, req: &mut Request, mut res: Response) -> Result<Response> {
+        let id = self.config.ensure(req);
+        if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+            res.headers_mut().insert(self.config.header.clone(), value);
+        }
+        Ok(res)
+    }
+
+    async fn catch(&self, req: &mut Request, err: Error) -> Result<Response> {
+        let id = self.config.ensure(req);
+        Err(self.config.attach(&id, err))
+    }
+}
+
+// Only IDs made of visible ASCII characters and of a reasonable length are accepted.
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_LENGTH && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+// Returns 64 pseudo-random bits.
+//
+// Request IDs don't need to be cryptographically secure, so the randomly seeded
+// std hasher is fed with a global counter and the current time.
+fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    hasher.write_u128(now.as_nanos());
+    hasher.finish()
+}
+
+fn uuid_v4() -> String {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&random_u64().to_be_bytes());
+    bytes[8..].copy_from_slice(&random_u64().to_be_bytes());
+    // Version 4 and RFC 4122 variant
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn ulid() -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let random = (u128::from(random_u64()) << 64 | u128::from(random_u64())) & ((1 << 80) - 1);
+    let value = (millis & ((1 << 48) - 1)) << 80 | random;
+
+    // 26 characters of 5 bits each, most significant first
+    (0..26)
+        .rev()
+        .map(|i| ALPHABET[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
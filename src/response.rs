@@ -0,0 +1,192 @@
+//! The response helpers module.
+//!
+//! It provides the [`ResponseExt`] trait which adds convenient constructors to [`Response`]
+//! for the most common kinds of responses. Failures are returned as [`Error`][`crate::Error`]s instead of panicking.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper::StatusCode;
+//! use hyper_middleware::{async_trait, Handler, Request, Response, ResponseExt, Result};
+//!
+//! struct Application {}
+//!
+//! #[async_trait]
+//! impl Handler for Application {
+//!     async fn handle(&self, req: &mut Request) -> Result<Response> {
+//!         match req.uri().path() {
+//!             "/" => Response::html("<h1>¡Hola!</h1>"),
+//!             "/old" => Response::redirect(StatusCode::MOVED_PERMANENTLY, "/"),
+//!             "/ping" => Response::no_content(),
+//!             "/license" => Response::file("LICENSE-MIT").await,
+//!             _ => Response::text("Not here"),
+//!         }
+//!     }
+//! }
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{self, HeaderValue};
+use hyper::StatusCode;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::{
+    http_error_forbidden, http_error_internal_server_error, http_error_not_found, Body, Response,
+    Result,
+};
+
+#[async_trait]
+/// Convenient constructors for [`Response`]s.
+pub trait ResponseExt: Sized {
+    /// Create a `200 OK` response with a `text/plain` body.
+    fn text<B>(body: B) -> Result<Self>
+    where
+        B: Into<Body>;
+
+    /// Create a `200 OK` response with a `text/html` body.
+    fn html<B>(body: B) -> Result<Self>
+    where
+        B: Into<Body>;
+
+    /// Create a `200 OK` response with a value serialized as an `application/json` body.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    fn json<T>(value: &T) -> Result<Self>
+    where
+        T: serde::Serialize + ?Sized;
+
+    /// Create a redirect response to the given location.
+    ///
+    /// It returns an error if the status code is not a `3xx` one or the location is not a valid header value.
+    fn redirect(status: StatusCode, location: &str) -> Result<Self>;
+
+    /// Create an empty `204 No Content` response.
+    fn no_content() -> Result<Self>;
+
+    /// Create a `200 OK` response with the contents of a file and its guessed `Content-Type`.
+    ///
+    /// The whole file is read into memory so it's intended for small files only.
+    /// It returns a `404 Not Found` or `403 Forbidden` error if the file does not exist or cannot be accessed.
+    async fn file<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path> + Send;
+}
+
+#[async_trait]
+impl ResponseExt for Response {
+    fn text<B>(body: B) -> Result<Self>
+    where
+        B: Into<Body>,
+    {
+        with_content_type(body.into(), "text/plain; charset=utf-8")
+    }
+
+    fn html<B>(body: B) -> Result<Self>
+    where
+        B: Into<Body>,
+    {
+        with_content_type(body.into(), "text/html; charset=utf-8")
+    }
+
+    #[cfg(feature = "json")]
+    fn json<T>(value: &T) -> Result<Self>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let body = serde_json::to_vec(value).map_err(|err| {
+            http_error_internal_server_error!("unable to serialize the JSON response: {}", err)
+        })?;
+        with_content_type(Body::from(body), "application/json")
+    }
+
+    fn redirect(status: StatusCode, location: &str) -> Result<Self> {
+        if !status.is_redirection() {
+            return Err(http_error_internal_server_error!(
+                "status `{}` is not a redirection",
+                status
+            ));
+        }
+        let location = HeaderValue::from_str(location).map_err(|err| {
+            http_error_internal_server_error!("invalid redirect location `{}`: {}", location, err)
+        })?;
+        Ok(Response::builder()
+            .status(status)
+            .header(header::LOCATION, location)
+            .body(Body::empty())?)
+    }
+
+    fn no_content() -> Result<Self> {
+        Ok(Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Body::empty())?)
+    }
+
+    async fn file<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path> + Send,
+    {
+        let path = path.as_ref();
+        let contents = match tokio::fs::read(path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Err(http_error_not_found!("file `{}` not found", path.display()))
+            }
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                return Err(http_error_forbidden!(
+                    "file `{}` cannot be accessed",
+                    path.display()
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        with_content_type(Body::from(contents), guess_mime(path))
+    }
+}
+
+fn with_content_type(body: Body, content_type: &'static str) -> Result<Response> {
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, HeaderValue::from_static(content_type))
+        .body(body)?)
+}
+
+/// Guess the MIME type of a file by its extension, `application/octet-stream` otherwise.
+pub(crate) fn guess_mime(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,666 @@
+//! The path router module.
+//!
+//! It provides a [`Router`] which dispatches requests to other [`Handler`]s
+//! based on the request path and method. A `Router` is a [`Handler`] itself so it can be
+//! wrapped by a [`Middlewares`][`super::Middlewares`] chain or hosted by a [`Service`][`super::Service`] directly.
+//!
+//! Route paths support the following segments:
+//!
+//! - Static segments like `/users`.
+//! - Named parameters like `/users/:id` which match exactly one segment.
+//! - Named wildcards like `/static/*rest` which match the rest of the path (zero or more segments).
+//!   A wildcard should be the last segment of a route path.
+//!
+//! When several routes match a path, the most specific one wins.
+//! That is, static segments take precedence over parameters and parameters take precedence over wildcards.
+//!
+//! Captured values are stored in the request extensions as [`Params`]
+//! so downstream handlers can access them. The pattern of the matched route (e.g. `/users/:id`)
+//! is stored as [`MatchedPath`] as well, which is useful for low-cardinality labels of logs or metrics.
+//!
+//! ## Mounting
+//!
+//! Other handlers can be mounted under a path prefix via [`Router::mount`].
+//! A mounted handler receives the request with the matched prefix stripped from its URI
+//! whereas the original URI is kept in the request extensions as [`OriginalUri`].
+//! Since a [`Middlewares`][`super::Middlewares`] chain is a `Handler` as well, this is the way to
+//! attach a middleware stack to a sub-tree of routes (e.g. an authorization step only under `/admin`).
+//! The same applies to single routes, a `Middlewares` chain can be registered for a given path and method.
+//!
+//! Routes take precedence over mounted handlers.
+//! If no route matches the request path then a `404 Not Found` error is returned.
+//! If a route matches the request path but not its method then a `405 Method Not Allowed` error
+//! including an `Allow` header is returned instead.
+//!
+//! ## Examples
+//!
+//! a. Dispatch requests by path and method.
+//!
+//! ```rust
+//! use hyper::Server;
+//! use hyper_middleware::{Body, Middlewares, Params, Request, Response, Result, Router, Service};
+//!
+//! fn user(req: &mut Request) -> Result<Response> {
+//!     let params = req.extensions().get::<Params>().unwrap();
+//!     let id = params.get("id").unwrap_or_default();
+//!     Ok(Response::new(Body::from(format!("User: {}", id))))
+//! }
+//!
+//! fn assets(req: &mut Request) -> Result<Response> {
+//!     let params = req.extensions().get::<Params>().unwrap();
+//!     let rest = params.get("rest").unwrap_or_default();
+//!     Ok(Response::new(Body::from(format!("Asset: {}", rest))))
+//! }
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() -> Result {
+//!     let mut router = Router::new();
+//!     router
+//!         .get("/users/:id", user)
+//!         .get("/static/*rest", assets);
+//!
+//!     let middlewares = Middlewares::new(router);
+//!     let service = Service::new(middlewares);
+//!
+//!     let addr = ([127, 0, 0, 1], 0).into();
+//!     let server = Server::bind(&addr).serve(service);
+//!
+//!     println!("Listening on http://{}", server.local_addr());
+//!
+//!     // server.await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! b. Mount a router with its own middlewares under a path prefix.
+//!
+//! ```rust
+//! use hyper::StatusCode;
+//! use hyper_middleware::{
+//!     http_error_unauthorized, Body, Middlewares, OriginalUri, Request, Response, Result, Router,
+//! };
+//!
+//! fn dashboard(req: &mut Request) -> Result<Response> {
+//!     // The path here is `/dashboard` while the original one is `/admin/dashboard`
+//!     let original = req.extensions().get::<OriginalUri>().unwrap();
+//!     Ok(Response::new(Body::from(format!("{} - {}", req.uri().path(), original.0.path()))))
+//! }
+//!
+//! fn authorize(req: &mut Request) -> Result {
+//!     match req.headers().get(hyper::header::AUTHORIZATION) {
+//!         Some(_) => Ok(()),
+//!         None => Err(http_error_unauthorized!("authorization required")),
+//!     }
+//! }
+//!
+//! let mut admin = Router::new();
+//! admin.get("/dashboard", dashboard);
+//!
+//! let mut admin = Middlewares::new(admin);
+//! admin.link_before(authorize);
+//!
+//! let mut router = Router::new();
+//! router.mount("/admin", admin);
+//! ```
+
+use async_trait::async_trait;
+use hyper::header::{HeaderValue, ALLOW};
+use hyper::{Method, StatusCode, Uri};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use crate::error::Context;
+use crate::middleware::Handler;
+use crate::{http_error_method_not_allowed, http_error_not_found};
+use crate::{Request, Response, Result};
+
+/// The original request URI before a [`Router`] stripped the prefix of a mounted handler.
+///
+/// It is stored in the request extensions by the outermost mount point so nested
+/// mounts don't overwrite it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalUri(pub Uri);
+
+/// The path pattern of the route which matched the request (e.g. `/users/:id`).
+///
+/// It is stored in the request extensions by a [`Router`]. Mount prefixes are included,
+/// so a `/:id` route of a router mounted under `/users` results in `/users/:id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedPath(pub String);
+
+impl MatchedPath {
+    /// Returns the matched path pattern.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The parameters captured from the request path by a [`Router`].
+///
+/// They are stored in the request extensions once a route matches.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Params {
+    entries: Vec<(String, String)>,
+}
+
+impl Params {
+    /// Create an empty set of parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the (percent-decoded) value of a parameter by its name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Adds a parameter. A parameter with the same name added later takes precedence.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// Returns an iterator over the parameters in their insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of parameters.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Static(String),
+    Param(String),
+    Wildcard(String),
+}
+
+impl Segment {
+    // Lower is more specific.
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Param(_) => 1,
+            Segment::Wildcard(_) => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    fn parse(path: &str) -> Self {
+        let parts: Vec<&str> = split_path(path).collect();
+        let mut segments = Vec::with_capacity(parts.len());
+
+        for (i, part) in parts.iter().enumerate() {
+            let segment = if let Some(name) = part.strip_prefix(':') {
+                assert!(
+                    !name.is_empty(),
+                    "route `{}` has an unnamed parameter",
+                    path
+                );
+                Segment::Param(name.to_owned())
+            } else if let Some(name) = part.strip_prefix('*') {
+                assert!(!name.is_empty(), "route `{}` has an unnamed wildcard", path);
+                assert!(
+                    i == parts.len() - 1,
+                    "route `{}` has a wildcard which is not the last segment",
+                    path
+                );
+                Segment::Wildcard(name.to_owned())
+            } else {
+                Segment::Static((*part).to_owned())
+            };
+            segments.push(segment);
+        }
+
+        Self { segments }
+    }
+
+    // Returns the path representation of the pattern.
+    fn to_path(&self) -> String {
+        let segments: Vec<String> = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Static(value) => value.clone(),
+                Segment::Param(name) => format!(":{}", name),
+                Segment::Wildcard(name) => format!("*{}", name),
+            })
+            .collect();
+        format!("/{}", segments.join("/"))
+    }
+
+    fn matches(&self, path: &str) -> Option<Params> {
+        let parts: Vec<&str> = split_path(path).collect();
+        let mut params = Params::new();
+
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Static(value) => match parts.get(i) {
+                    Some(part) if part == value => {}
+                    _ => return None,
+                },
+                Segment::Param(name) => match parts.get(i) {
+                    Some(part) => params.insert(name.as_str(), percent_decode(part)),
+                    None => return None,
+                },
+                Segment::Wildcard(name) => {
+                    let rest = parts.get(i..).unwrap_or_default();
+                    let rest: Vec<String> = rest.iter().map(|p| percent_decode(p)).collect();
+                    params.insert(name.as_str(), rest.join("/"));
+                    return Some(params);
+                }
+            }
+        }
+
+        if parts.len() == self.segments.len() {
+            Some(params)
+        } else {
+            None
+        }
+    }
+
+    // Match only the leading segments of a path. Wildcards are not allowed here.
+    fn matches_prefix(&self, path: &str) -> Option<Params> {
+        let parts: Vec<&str> = split_path(path).collect();
+        if parts.len() < self.segments.len() {
+            return None;
+        }
+
+        let mut params = Params::new();
+        for (segment, part) in self.segments.iter().zip(parts.iter()) {
+            match segment {
+                Segment::Static(value) if value == part => {}
+                Segment::Param(name) => params.insert(name.as_str(), percent_decode(part)),
+                _ => return None,
+            }
+        }
+        Some(params)
+    }
+
+    // Compare the specificity of two patterns segment by segment.
+    fn specificity(&self, other: &Self) -> Ordering {
+        for (a, b) in self.segments.iter().zip(other.segments.iter()) {
+            match a.rank().cmp(&b.rank()) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
+        }
+        other.segments.len().cmp(&self.segments.len())
+    }
+}
+
+struct Route {
+    pattern: Pattern,
+    handlers: BTreeMap<MethodKey, Box<dyn Handler>>,
+}
+
+// Wrapper which gives `Method` an ordering so it can be stored in a `BTreeMap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MethodKey(Method);
+
+impl PartialOrd for MethodKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MethodKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.as_str().cmp(other.0.as_str())
+    }
+}
+
+impl Route {
+    fn handler(&self, method: &Method) -> Option<&dyn Handler> {
+        let handler = self.handlers.get(&MethodKey(method.clone()));
+        match handler {
+            Some(handler) => Some(handler.as_ref()),
+            // `HEAD` requests fall back to `GET` handlers
+            None if method == Method::HEAD => self
+                .handlers
+                .get(&MethodKey(Method::GET))
+                .map(|h| h.as_ref()),
+            None => None,
+        }
+    }
+
+    fn allowed_methods(&self) -> Vec<Method> {
+        let mut methods: Vec<Method> = self.handlers.keys().map(|k| k.0.clone()).collect();
+        if methods.contains(&Method::GET) && !methods.contains(&Method::HEAD) {
+            methods.push(Method::HEAD);
+        }
+        methods
+    }
+}
+
+struct Mount {
+    pattern: Pattern,
+    handler: Box<dyn Handler>,
+}
+
+/// A [`Handler`] which dispatches requests by path and method to other handlers.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    mounts: Vec<Mount>,
+}
+
+impl Router {
+    /// Create a new empty router.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a `Handler` for the given method and path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path contains an unnamed parameter or wildcard or
+    /// if a wildcard is not the last path segment.
+    pub fn route<H>(&mut self, method: Method, path: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        let pattern = Pattern::parse(path);
+        let handler = Box::new(handler) as Box<dyn Handler>;
+        let key = MethodKey(method);
+
+        match self.routes.iter_mut().find(|r| r.pattern == pattern) {
+            Some(route) => {
+                route.handlers.insert(key, handler);
+            }
+            None => {
+                let mut handlers = BTreeMap::new();
+                handlers.insert(key, handler);
+                self.routes.push(Route { pattern, handlers });
+            }
+        }
+        self
+    }
+
+    /// Mount a `Handler` under the given path prefix for any method.
+    ///
+    /// The matched prefix is stripped from the request URI before calling the handler
+    /// and restored once it returns. The original URI is available in the request extensions as [`OriginalUri`].
+    /// The prefix can contain parameters (e.g. `/tenants/:tenant`) which are captured as usual.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the prefix contains an unnamed parameter or a wildcard.
+    pub fn mount<H>(&mut self, prefix: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        let pattern = Pattern::parse(prefix);
+        assert!(
+            !pattern
+                .segments
+                .iter()
+                .any(|s| matches!(s, Segment::Wildcard(_))),
+            "mount prefix `{}` cannot contain a wildcard",
+            prefix
+        );
+        self.mounts.push(Mount {
+            pattern,
+            handler: Box::new(handler) as Box<dyn Handler>,
+        });
+        self
+    }
+
+    /// Register a `Handler` for `GET` requests on the given path.
+    ///
+    /// Note that `HEAD` requests are also handled by it unless a `HEAD` handler is registered.
+    pub fn get<H>(&mut self, path: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        self.route(Method::GET, path, handler)
+    }
+
+    /// Register a `Handler` for `HEAD` requests on the given path.
+    pub fn head<H>(&mut self, path: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        self.route(Method::HEAD, path, handler)
+    }
+
+    /// Register a `Handler` for `POST` requests on the given path.
+    pub fn post<H>(&mut self, path: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        self.route(Method::POST, path, handler)
+    }
+
+    /// Register a `Handler` for `PUT` requests on the given path.
+    pub fn put<H>(&mut self, path: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        self.route(Method::PUT, path, handler)
+    }
+
+    /// Register a `Handler` for `PATCH` requests on the given path.
+    pub fn patch<H>(&mut self, path: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        self.route(Method::PATCH, path, handler)
+    }
+
+    /// Register a `Handler` for `DELETE` requests on the given path.
+    pub fn delete<H>(&mut self, path: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        self.route(Method::DELETE, path, handler)
+    }
+
+    /// Register a `Handler` for `OPTIONS` requests on the given path.
+    pub fn options<H>(&mut self, path: &str, handler: H) -> &mut Router
+    where
+        H: Handler,
+    {
+        self.route(Method::OPTIONS, path, handler)
+    }
+}
+
+impl Router {
+    // Call a mounted handler with the matched prefix stripped from the request URI.
+    async fn handle_mount(
+        &self,
+        req: &mut Request,
+        mount: &Mount,
+        params: Params,
+    ) -> Result<Response> {
+        let original = req.uri().clone();
+        let path = strip_segments(original.path(), mount.pattern.segments.len());
+        let uri = replace_path(&original, &path)
+            .with_context(|| format!("unable to strip the prefix of `{}`", original))?;
+
+        if req.extensions().get::<OriginalUri>().is_none() {
+            req.extensions_mut().insert(OriginalUri(original.clone()));
+        }
+        merge_params(req, params);
+        merge_matched_path(req, &mount.pattern);
+
+        *req.uri_mut() = uri;
+        let result = mount.handler.handle(req).await;
+        *req.uri_mut() = original;
+
+        result
+    }
+}
+
+#[async_trait]
+impl Handler for Router {
+    async fn handle(&self, req: &mut Request) -> Result<Response> {
+        let path = req.uri().path().to_owned();
+        let mut matched: Vec<(&Route, Params)> = self
+            .routes
+            .iter()
+            .filter_map(|route| route.pattern.matches(&path).map(|params| (route, params)))
+            .collect();
+
+        // Most specific routes first
+        matched.sort_by(|(a, _), (b, _)| a.pattern.specificity(&b.pattern));
+
+        let method = req.method().clone();
+        let found = matched
+            .iter()
+            .find_map(|(route, params)| route.handler(&method).map(|h| (*route, h, params)));
+
+        if let Some((route, handler, params)) = found {
+            merge_matched_path(req, &route.pattern);
+            merge_params(req, params.clone());
+            return handler.handle(req).await;
+        }
+
+        let mut mounts: Vec<(&Mount, Params)> = self
+            .mounts
+            .iter()
+            .filter_map(|m| m.pattern.matches_prefix(&path).map(|params| (m, params)))
+            .collect();
+        mounts.sort_by(|(a, _), (b, _)| a.pattern.specificity(&b.pattern));
+
+        if let Some((mount, params)) = mounts.into_iter().next() {
+            return self.handle_mount(req, mount, params).await;
+        }
+
+        if matched.is_empty() {
+            return Err(http_error_not_found!("no route matches path `{}`", path));
+        }
+
+        let mut allowed: Vec<Method> = vec![];
+        for (route, _) in &matched {
+            for m in route.allowed_methods() {
+                if !allowed.contains(&m) {
+                    allowed.push(m);
+                }
+            }
+        }
+        let allow = allowed
+            .iter()
+            .map(|m| m.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let err = http_error_method_not_allowed!(
+            "method `{}` is not allowed for path `{}`",
+            method,
+            path
+        );
+        Err(match HeaderValue::from_str(&allow) {
+            Ok(allow) => err.with_header(ALLOW, allow),
+            Err(_) => err,
+        })
+    }
+}
+
+// Add the captured parameters to the ones already stored in the request extensions (if any).
+fn merge_params(req: &mut Request, params: Params) {
+    match req.extensions_mut().get_mut::<Params>() {
+        Some(existing) => existing.entries.extend(params.entries),
+        None => {
+            req.extensions_mut().insert(params);
+        }
+    }
+}
+
+// Append the given pattern to the matched path stored in the request extensions (if any).
+fn merge_matched_path(req: &mut Request, pattern: &Pattern) {
+    let path = pattern.to_path();
+    match req.extensions_mut().get_mut::<MatchedPath>() {
+        Some(existing) => {
+            if path != "/" {
+                let prefix = existing.0.trim_end_matches('/').to_owned();
+                existing.0 = format!("{}{}", prefix, path);
+            }
+        }
+        None => {
+            req.extensions_mut().insert(MatchedPath(path));
+        }
+    }
+}
+
+// Remove the given number of leading segments from a path.
+fn strip_segments(path: &str, count: usize) -> String {
+    let mut rest = path;
+    for _ in 0..count {
+        rest = rest.trim_start_matches('/');
+        rest = match rest.find('/') {
+            Some(i) => &rest[i..],
+            None => "",
+        };
+    }
+    if rest.starts_with('/') {
+        rest.to_owned()
+    } else {
+        format!("/{}", rest)
+    }
+}
+
+// Build a new URI with the given path preserving the rest of its parts.
+fn replace_path(uri: &Uri, path: &str) -> std::result::Result<Uri, hyper::http::Error> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_owned(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse()?);
+    Ok(Uri::from_parts(parts)?)
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+// Decode a percent-encoded path segment. The raw segment is returned if the result is not valid UTF-8.
+pub(crate) fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            decoded.push(hex_value(bytes[i + 1]) << 4 | hex_value(bytes[i + 2]));
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| segment.to_owned())
+}
+
+// The value of an ASCII hexadecimal digit.
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
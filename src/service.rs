@@ -5,6 +5,17 @@
 //!
 //! The service allows to bind a [`Middlewares`][`super::Middlewares`] of middlewares.
 //!
+//! Errors returned by the hosted handler are converted into HTTP responses
+//! via an [`ErrorRenderer`][`super::ErrorRenderer`] so clients always receive a status code.
+//! By default the [`DefaultErrorRenderer`][`super::DefaultErrorRenderer`] is used
+//! but a custom one can be set via [`Service::with_error_renderer`].
+//!
+//! Every request gets the information of its connection in its extensions.
+//! See the [`connection`][`super::connection`] module for more details.
+//!
+//! The service also keeps track of its connections and requests via a [`Shutdown`][`super::Shutdown`] controller
+//! which allows to shut down a server gracefully. See the [`shutdown`][`super::shutdown`] module for more details.
+//!
 //! ## Example
 //!
 //! ```rust
@@ -29,10 +40,10 @@
 //!
 //!     let service = Service::new(middlewares);
 //!
-//!     let addr = ([127, 0, 0, 1], 8087).into();
+//!     let addr = ([127, 0, 0, 1], 0).into();
 //!     let server = Server::bind(&addr).serve(service);
 //!
-//!     println!("Listening on http://{}", addr);
+//!     println!("Listening on http://{}", server.local_addr());
 //!
 //!     // server.await?;
 //!
@@ -46,8 +57,10 @@ use std::future::{ready, Ready};
 use std::task::{Context, Poll};
 
 use self::handler_service::{HandlerService, HandlerServiceBuilder};
+use crate::connection::ConnectionInfo;
+use crate::error::ErrorRenderer;
 use crate::middleware::Handler;
-use crate::remote_addr::RemoteAddr;
+use crate::shutdown::Shutdown;
 
 /// A [Hyper Service][`hyper::service::Service`] entry point which hosts a [`Handler`].
 pub struct Service<H> {
@@ -64,12 +77,44 @@ where
             builder: HandlerServiceBuilder::new(handler),
         }
     }
+
+    /// Set a custom [`ErrorRenderer`] used to convert handler errors into HTTP responses.
+    pub fn with_error_renderer<R>(mut self, renderer: R) -> Self
+    where
+        R: ErrorRenderer,
+    {
+        self.builder.set_error_renderer(renderer);
+        self
+    }
+
+    /// Add a typed state value which will be inserted into the extensions of every request
+    /// as [`State<T>`][`crate::State`].
+    pub fn with_state<T>(mut self, value: T) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        self.builder.add_state(value);
+        self
+    }
+
+    /// Set the [`Shutdown`] controller which tracks the connections and requests of this service.
+    ///
+    /// It allows sharing the same controller between several services (e.g. multiple listeners).
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.builder.set_shutdown(shutdown);
+        self
+    }
+
+    /// Returns the [`Shutdown`] controller of this service.
+    pub fn shutdown(&self) -> Shutdown {
+        self.builder.shutdown()
+    }
 }
 
 impl<H, T> HyperService<&T> for Service<H>
 where
     H: Handler,
-    T: RemoteAddr + Send + 'static,
+    T: ConnectionInfo + Send + 'static,
 {
     type Response = HandlerService<H>;
     type Error = Infallible;
@@ -80,25 +125,38 @@ where
     }
 
     fn call(&mut self, conn: &T) -> Self::Future {
-        ready(Ok(self.builder.build(conn.remote_addr())))
+        ready(Ok(self.builder.build(conn)))
     }
 }
 
 mod handler_service {
+    use std::convert::Infallible;
     use std::future::Future;
-    use std::net::SocketAddr;
     use std::pin::Pin;
     use std::sync::Arc;
     use std::task::{Context, Poll};
 
-    use crate::error::{Error, Result};
+    use hyper::StatusCode;
+
+    use crate::connection::{Connection, ConnectionExtensions, ConnectionInfo};
+    use crate::error::{DefaultErrorRenderer, ErrorRenderer};
+    use crate::future::race;
     use crate::http::{Request, Response};
+    use crate::http_error_service_unavailable;
     use crate::middleware::Handler;
     use crate::service::HyperService;
+    use crate::shutdown::{ConnectionGuard, Shutdown};
+    use crate::state::States;
+    use crate::trace::record_status;
 
     pub struct HandlerService<H> {
         handler: Arc<H>,
-        remote_addr: Option<SocketAddr>,
+        error_renderer: Arc<dyn ErrorRenderer>,
+        states: Arc<States>,
+        connection: Connection,
+        connection_extensions: ConnectionExtensions,
+        shutdown: Shutdown,
+        _connection: ConnectionGuard,
     }
 
     impl<H> HyperService<Request> for HandlerService<H>
@@ -106,24 +164,49 @@ mod handler_service {
         H: Handler,
     {
         type Response = Response;
-        type Error = Error;
-        type Future = Pin<Box<dyn Future<Output = Result<Response>> + Send + 'static>>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send + 'static>>;
 
-        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<()>> {
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
             Poll::Ready(Ok(()))
         }
 
         fn call(&mut self, mut req: Request) -> Self::Future {
-            if let Some(remote_addr) = self.remote_addr {
-                req.extensions_mut().insert(remote_addr);
-            }
+            self.connection.apply(&mut req);
+            self.connection_extensions.apply(&mut req);
+            self.states.apply(&mut req);
             let handler = self.handler.clone();
-            Box::pin(async move { handler.handle(&mut req).await })
+            let error_renderer = self.error_renderer.clone();
+            let shutdown = self.shutdown.clone();
+            #[cfg(feature = "tracing")]
+            let span = crate::trace::request_span(&req);
+            let fut = async move {
+                let _request = shutdown.request_guard();
+                let closed = async {
+                    shutdown.closed().await;
+                    Err(http_error_service_unavailable!(
+                        "the server is shutting down"
+                    ))
+                };
+                let result = race(handler.handle(&mut req), closed).await;
+                let res = match result {
+                    Ok(res) => res,
+                    Err(err) => error_renderer.render(&mut req, err).await,
+                };
+                record_status(&res);
+                Ok(res)
+            };
+            #[cfg(feature = "tracing")]
+            let fut = tracing::Instrument::instrument(fut, span);
+            Box::pin(fut)
         }
     }
 
     pub struct HandlerServiceBuilder<H> {
         handler: Arc<H>,
+        error_renderer: Arc<dyn ErrorRenderer>,
+        states: Arc<States>,
+        shutdown: Shutdown,
     }
 
     impl<H> HandlerServiceBuilder<H>
@@ -133,13 +216,46 @@ mod handler_service {
         pub fn new(handler: H) -> Self {
             Self {
                 handler: Arc::new(handler),
+                error_renderer: Arc::new(DefaultErrorRenderer),
+                states: Arc::new(States::default()),
+                shutdown: Shutdown::new(),
             }
         }
 
-        pub fn build(&self, remote_addr: Option<SocketAddr>) -> HandlerService<H> {
+        pub fn add_state<T>(&mut self, value: T)
+        where
+            T: Send + Sync + 'static,
+        {
+            Arc::make_mut(&mut self.states).insert(value);
+        }
+
+        pub fn set_shutdown(&mut self, shutdown: Shutdown) {
+            self.shutdown = shutdown;
+        }
+
+        pub fn shutdown(&self) -> Shutdown {
+            self.shutdown.clone()
+        }
+
+        pub fn set_error_renderer<R>(&mut self, renderer: R)
+        where
+            R: ErrorRenderer,
+        {
+            self.error_renderer = Arc::new(renderer);
+        }
+
+        pub fn build<T>(&self, conn: &T) -> HandlerService<H>
+        where
+            T: ConnectionInfo,
+        {
             HandlerService {
                 handler: self.handler.clone(),
-                remote_addr,
+                error_renderer: self.error_renderer.clone(),
+                states: self.states.clone(),
+                connection: Connection::from_info(conn),
+                connection_extensions: conn.extensions(),
+                shutdown: self.shutdown.clone(),
+                _connection: self.shutdown.connection_guard(),
             }
         }
     }
@@ -0,0 +1,211 @@
+//! The graceful shutdown module.
+//!
+//! It provides a [`Shutdown`] controller which keeps track of the connections and requests
+//! handled by a [`Service`][`super::Service`] and coordinates the shutdown of a Hyper server.
+//!
+//! The shutdown process consists of the following phases:
+//!
+//! 1. **Running**: connections are accepted and requests are handled as usual.
+//! 2. **Draining**: once [`Shutdown::trigger`] is called, the future returned by [`Shutdown::signal`] resolves
+//!    so the Hyper server stops accepting new connections and waits for the in-flight ones.
+//! 3. **Closed**: if the drain timeout passed to [`Shutdown::run`] expires, the requests still in progress
+//!    are cancelled with a `503 Service Unavailable` response and the server future is dropped.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper::Server;
+//! use hyper_middleware::{Body, Request, Response, Result, Service, Shutdown};
+//! use std::time::Duration;
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() -> Result {
+//!     let handler = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::from("¡Hola!"))) };
+//!
+//!     let shutdown = Shutdown::new();
+//!     let service = Service::new(handler).with_shutdown(shutdown.clone());
+//!
+//!     let addr = ([127, 0, 0, 1], 0).into();
+//!     let server = Server::bind(&addr).serve(service);
+//!
+//!     println!("Listening on http://{}", server.local_addr());
+//!
+//!     let server = server.with_graceful_shutdown(shutdown.signal());
+//!
+//!     // For example, stop the server on `Ctrl+C`
+//!     // tokio::spawn({
+//!     //     let shutdown = shutdown.clone();
+//!     //     async move {
+//!     //         tokio::signal::ctrl_c().await.ok();
+//!     //         shutdown.trigger();
+//!     //     }
+//!     // });
+//!     shutdown.trigger();
+//!
+//!     shutdown.run(server, Duration::from_secs(30)).await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::future::race;
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum State {
+    Running,
+    Draining,
+    Closed,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: watch::Sender<State>,
+    connections: AtomicUsize,
+    requests: AtomicUsize,
+}
+
+/// A shutdown controller shared by a [`Service`][`super::Service`] and the application.
+///
+/// It can be cheaply cloned, all clones control the same shutdown process.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    /// Create a new shutdown controller.
+    pub fn new() -> Self {
+        let (state, _) = watch::channel(State::Running);
+        Self {
+            inner: Arc::new(Inner {
+                state,
+                connections: AtomicUsize::new(0),
+                requests: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Start the shutdown process.
+    ///
+    /// The future returned by [`Shutdown::signal`] resolves right after.
+    pub fn trigger(&self) {
+        self.advance(State::Draining);
+    }
+
+    /// Returns `true` if the shutdown process was started.
+    pub fn is_triggered(&self) -> bool {
+        *self.inner.state.borrow() >= State::Draining
+    }
+
+    /// Returns a future which resolves once the shutdown process was started.
+    ///
+    /// It is intended to be passed to the Hyper `Server::with_graceful_shutdown` method.
+    pub fn signal(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.wait_for(State::Draining)
+    }
+
+    /// Returns the number of open connections.
+    pub fn active_connections(&self) -> usize {
+        self.inner.connections.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of requests in progress.
+    pub fn active_requests(&self) -> usize {
+        self.inner.requests.load(Ordering::SeqCst)
+    }
+
+    /// Drive a Hyper server future until it completes or the shutdown process finishes.
+    ///
+    /// Once the shutdown was triggered, the server has `drain_timeout` time to finish its in-flight requests.
+    /// After that, the requests still in progress are cancelled with a `503 Service Unavailable`
+    /// response and the server future is dropped.
+    pub async fn run<F>(&self, server: F, drain_timeout: Duration) -> Result
+    where
+        F: Future<Output = hyper::Result<()>>,
+    {
+        let mut server = Box::pin(server);
+
+        let signal = async {
+            self.signal().await;
+            None
+        };
+        if let Some(res) = race(async { Some((&mut server).await) }, signal).await {
+            return Ok(res?);
+        }
+
+        match tokio::time::timeout(drain_timeout, &mut server).await {
+            Ok(res) => Ok(res?),
+            Err(_) => {
+                self.advance(State::Closed);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns a future which resolves once the drain timeout expired.
+    pub(crate) fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.wait_for(State::Closed)
+    }
+
+    pub(crate) fn connection_guard(&self) -> ConnectionGuard {
+        self.inner.connections.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard(self.clone())
+    }
+
+    pub(crate) fn request_guard(&self) -> RequestGuard {
+        self.inner.requests.fetch_add(1, Ordering::SeqCst);
+        RequestGuard(self.clone())
+    }
+
+    fn advance(&self, state: State) {
+        self.inner.state.send_if_modified(|current| {
+            if *current < state {
+                *current = state;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    fn wait_for(&self, state: State) -> impl Future<Output = ()> + Send + 'static {
+        let mut rx = self.inner.state.subscribe();
+        async move {
+            // It also resolves if every controller was dropped in the meantime
+            rx.wait_for(|current| *current >= state).await.ok();
+        }
+    }
+}
+
+/// Keeps a connection counted while alive.
+#[derive(Debug)]
+pub(crate) struct ConnectionGuard(Shutdown);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.inner.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Keeps a request counted while alive.
+#[derive(Debug)]
+pub(crate) struct RequestGuard(Shutdown);
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        self.0.inner.requests.fetch_sub(1, Ordering::SeqCst);
+    }
+}
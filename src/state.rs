@@ -0,0 +1,156 @@
+//! The application state module.
+//!
+//! It provides a [`State`] type which allows sharing typed values (e.g. a configuration or a database pool)
+//! with every `Handler`, `BeforeMiddleware` and `AfterMiddleware`.
+//!
+//! State values are registered once via [`Service::with_state`][`super::Service::with_state`]
+//! or [`Middlewares::add_state`][`super::Middlewares::add_state`] and then inserted into the extensions
+//! of every request as `State<T>`, so they can be fetched by type.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper::Server;
+//! use hyper_middleware::{
+//!     async_trait, Body, Handler, Middlewares, Request, Response, Result, Service, State,
+//! };
+//! use std::path::PathBuf;
+//!
+//! struct Config {
+//!     pub root: PathBuf,
+//! }
+//!
+//! struct Application {}
+//!
+//! #[async_trait]
+//! impl Handler for Application {
+//!     async fn handle(&self, req: &mut Request) -> Result<Response> {
+//!         let config = State::<Config>::from_request(req)?;
+//!         Ok(Response::new(Body::from(config.root.display().to_string())))
+//!     }
+//! }
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() -> Result {
+//!     let config = Config {
+//!         root: std::env::current_dir().unwrap(),
+//!     };
+//!
+//!     let middlewares = Middlewares::new(Application {});
+//!     let service = Service::new(middlewares).with_state(config);
+//!
+//!     let addr = ([127, 0, 0, 1], 0).into();
+//!     let server = Server::bind(&addr).serve(service);
+//!
+//!     println!("Listening on http://{}", server.local_addr());
+//!
+//!     // server.await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use hyper::http::Extensions;
+use hyper::StatusCode;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::{http_error_internal_server_error, Request, Result};
+
+/// A typed application state value shared by every request.
+///
+/// It dereferences to the underlying value and can be cheaply cloned.
+pub struct State<T>(Arc<T>);
+
+impl<T> State<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Create a new state value.
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+
+    /// Fetch a state value by type from the request extensions.
+    ///
+    /// It returns a `500 Internal Server Error` if no value of the given type was registered.
+    pub fn from_request(req: &Request) -> Result<Self> {
+        match req.extensions().get::<State<T>>() {
+            Some(state) => Ok(state.clone()),
+            None => Err(http_error_internal_server_error!(
+                "state of type `{}` is not registered",
+                std::any::type_name::<T>()
+            )),
+        }
+    }
+
+    /// Returns a reference-counted pointer to the underlying value.
+    pub fn inner(&self) -> Arc<T> {
+        self.0.clone()
+    }
+}
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Deref for State<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> fmt::Debug for State<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("State").field(&self.0).finish()
+    }
+}
+
+type Inserter = Arc<dyn Fn(&mut Extensions) + Send + Sync>;
+
+/// A collection of typed values which are inserted into the request extensions.
+#[derive(Default, Clone)]
+pub(crate) struct States {
+    inserters: Vec<Inserter>,
+}
+
+impl States {
+    pub(crate) fn insert<T>(&mut self, value: T)
+    where
+        T: Send + Sync + 'static,
+    {
+        self.insert_value(State::new(value));
+    }
+
+    // Insert a value as is instead of wrapping it into a `State`.
+    pub(crate) fn insert_value<T>(&mut self, value: T)
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        self.inserters.push(Arc::new(move |extensions| {
+            extensions.insert(value.clone());
+        }));
+    }
+
+    pub(crate) fn extend(&mut self, other: States) {
+        self.inserters.extend(other.inserters);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.inserters.len()
+    }
+
+    pub(crate) fn apply(&self, req: &mut Request) {
+        for insert in &self.inserters {
+            insert(req.extensions_mut());
+        }
+    }
+}
@@ -0,0 +1,667 @@
+//! The static file serving module.
+//!
+//! It provides a [`StaticFiles`] handler which serves the files of a directory root.
+//!
+//! - Request paths are percent-decoded and resolved against the root. Paths trying to escape it
+//!   (e.g. via `..` segments or symbolic links pointing outside of the root) are rejected with a `404 Not Found` error.
+//! - The `Content-Type` is guessed from the file extension.
+//! - Responses carry `ETag` and `Last-Modified` validators and the `If-None-Match` and `If-Modified-Since`
+//!   conditional requests are answered with a `304 Not Modified` response.
+//! - Single and multiple `Range` requests are supported (the latter as `multipart/byteranges`) honoring `If-Range`.
+//!   Ranges which cannot be satisfied result in a `416 Range Not Satisfiable` error.
+//! - Directories are served via their index files (`index.html` by default)
+//!   or optionally as an HTML directory listing.
+//! - File contents are streamed instead of being loaded into memory.
+//! - Optionally, pre-compressed sidecar files (e.g. `app.js.br` next to `app.js`) are served
+//!   according to the `Accept-Encoding` request header. See [`StaticFiles::precompressed`].
+//!
+//! Only `GET` and `HEAD` requests are allowed, other methods result in a `405 Method Not Allowed` error.
+//!
+//! The handler serves the request URI path as is, so it can be mounted under a path prefix via a
+//! [`Router`][`crate::Router`] which strips the prefix before calling it.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper_middleware::{Middlewares, Router, StaticFiles};
+//!
+//! let mut router = Router::new();
+//! router.mount(
+//!     "/assets",
+//!     StaticFiles::new("./public")
+//!         .directory_listing(true)
+//!         .precompressed(true),
+//! );
+//!
+//! let middlewares = Middlewares::new(router);
+//! ```
+
+use async_trait::async_trait;
+use futures_util::stream;
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{Method, StatusCode};
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::fs::Metadata;
+use std::io::{self, ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::encoding::{negotiate, Encoding};
+use crate::headers::add_vary;
+use crate::middleware::Handler;
+use crate::response::guess_mime;
+use crate::router::{percent_decode, OriginalUri};
+use crate::{
+    http_error_forbidden, http_error_internal_server_error, http_error_method_not_allowed,
+    http_error_not_found, http_error_range_not_satisfiable, Body, Error, Request, Response, Result,
+};
+
+/// The maximum number of ranges accepted in a single `Range` header.
+/// Requests with more ranges are served as a whole.
+const MAX_RANGES: usize = 32;
+
+/// The size of the chunks read from files.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The codings of the pre-compressed sidecar files, in server preference order.
+const PRECOMPRESSED: &[Encoding] = &[Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+/// The multipart boundary used by `multipart/byteranges` responses.
+const BOUNDARY: &str = "3d6b6a416f9b5";
+
+/// A [`Handler`] which serves the files of a directory root.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    index_files: Vec<String>,
+    directory_listing: bool,
+    precompressed: bool,
+}
+
+impl StaticFiles {
+    /// Create a new handler serving the files of the given directory root.
+    pub fn new<P>(root: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            root: root.into(),
+            index_files: vec!["index.html".to_owned()],
+            directory_listing: false,
+            precompressed: false,
+        }
+    }
+
+    /// Set the file names looked up, in order, when a directory is requested.
+    ///
+    /// Defaults to `index.html`.
+    pub fn index_files<I, S>(mut self, index_files: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.index_files = index_files.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Render an HTML listing of the requested directory when it has no index file.
+    ///
+    /// Disabled by default.
+    pub fn directory_listing(mut self, enabled: bool) -> Self {
+        self.directory_listing = enabled;
+        self
+    }
+
+    /// Serve pre-compressed sidecar files when the client accepts their coding.
+    ///
+    /// For a requested file like `app.js`, the `app.js.br`, `app.js.zst` and `app.js.gz` siblings
+    /// are looked up (in that preference order) and the best one accepted by the `Accept-Encoding`
+    /// request header is served with the matching `Content-Encoding` and the `Content-Type` of the original file.
+    /// If none is accepted or exists, the plain file is served.
+    /// Responses of files having sidecars get a `Vary: Accept-Encoding` header.
+    ///
+    /// Disabled by default.
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    // Resolve the request path against the root, rejecting paths which escape it.
+    async fn resolve(&self, path: &str) -> Result<(PathBuf, Metadata)> {
+        let mut resolved = self.root.clone();
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            let segment = percent_decode(segment);
+            if segment == "." {
+                continue;
+            }
+            if segment == ".."
+                || segment.contains('/')
+                || segment.contains('\\')
+                || segment.contains('\0')
+                || Path::new(&segment).is_absolute()
+            {
+                return Err(http_error_not_found!("path `{}` not found", path));
+            }
+            resolved.push(segment);
+        }
+
+        self.confine(&resolved)
+            .await
+            .map_err(|err| io_error(err, path))
+    }
+
+    // Canonicalize a path within the root, symbolic links pointing outside of it are reported as not found.
+    async fn confine(&self, path: &Path) -> io::Result<(PathBuf, Metadata)> {
+        let root = tokio::fs::canonicalize(&self.root).await?;
+        let canonical = tokio::fs::canonicalize(path).await?;
+        if !canonical.starts_with(&root) {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                "path escapes the root directory",
+            ));
+        }
+        let metadata = tokio::fs::metadata(&canonical).await?;
+        Ok((canonical, metadata))
+    }
+
+    // Serve a directory via its index files or a listing.
+    async fn serve_dir(&self, req: &Request, dir: &Path) -> Result<Response> {
+        let path = req.uri().path();
+        if !path.ends_with('/') {
+            return redirect_to_dir(req);
+        }
+
+        for index in &self.index_files {
+            if let Ok((index_path, metadata)) = self.confine(&dir.join(index)).await {
+                if metadata.is_file() {
+                    return self.serve(req, &index_path, &metadata).await;
+                }
+            }
+        }
+
+        if self.directory_listing {
+            return render_listing(req, dir).await;
+        }
+
+        Err(http_error_not_found!("directory `{}` has no index", path))
+    }
+
+    // Serve a file or its best pre-compressed variant.
+    async fn serve(&self, req: &Request, path: &Path, metadata: &Metadata) -> Result<Response> {
+        let content_type = guess_mime(path);
+        if !self.precompressed {
+            return serve_file(req, path, metadata, content_type, None).await;
+        }
+
+        let mut variants = vec![];
+        for encoding in PRECOMPRESSED {
+            let mut variant = OsString::from(path.as_os_str());
+            variant.push(".");
+            variant.push(encoding.extension());
+            // Sidecars could be symbolic links pointing outside of the root too
+            if let Ok((variant, variant_metadata)) = self.confine(Path::new(&variant)).await {
+                if variant_metadata.is_file() {
+                    variants.push((*encoding, variant, variant_metadata));
+                }
+            }
+        }
+        if variants.is_empty() {
+            return serve_file(req, path, metadata, content_type, None).await;
+        }
+
+        let available: Vec<Encoding> = variants.iter().map(|(e, _, _)| *e).collect();
+        let accept = req.headers().get(header::ACCEPT_ENCODING);
+        let chosen = negotiate(accept, &available)
+            .and_then(|chosen| variants.into_iter().find(|(e, _, _)| *e == chosen));
+
+        let mut res = match chosen {
+            Some((encoding, variant, variant_metadata)) => {
+                serve_file(
+                    req,
+                    &variant,
+                    &variant_metadata,
+                    content_type,
+                    Some(encoding),
+                )
+                .await?
+            }
+            None => serve_file(req, path, metadata, content_type, None).await?,
+        };
+        add_vary(res.headers_mut(), &["accept-encoding"]);
+        Ok(res)
+    }
+}
+
+#[async_trait]
+impl Handler for StaticFiles {
+    async fn handle(&self, req: &mut Request) -> Result<Response> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Err(http_error_method_not_allowed!(
+                "method `{}` is not allowed for static files",
+                req.method()
+            )
+            .with_header(header::ALLOW, HeaderValue::from_static("GET, HEAD")));
+        }
+
+        let (path, metadata) = self.resolve(req.uri().path()).await?;
+        if metadata.is_dir() {
+            return self.serve_dir(req, &path).await;
+        }
+
+        self.serve(req, &path, &metadata).await
+    }
+}
+
+/// Serve a single file honoring conditional and range requests.
+///
+/// The `encoding` is the content coding of an already encoded file (e.g. a pre-compressed sidecar).
+pub(crate) async fn serve_file(
+    req: &Request,
+    path: &Path,
+    metadata: &Metadata,
+    content_type: &'static str,
+    encoding: Option<Encoding>,
+) -> Result<Response> {
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = entity_tag(len, modified, encoding);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Some(encoding) = encoding {
+        headers.insert(header::CONTENT_ENCODING, encoding.to_header_value());
+    }
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, etag);
+    }
+    if let Some(modified) = modified {
+        if let Ok(date) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+            headers.insert(header::LAST_MODIFIED, date);
+        }
+    }
+
+    if is_not_modified(req.headers(), &etag, modified) {
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::NOT_MODIFIED;
+        *res.headers_mut() = headers;
+        return Ok(res);
+    }
+
+    let ranges = match req.headers().get(header::RANGE) {
+        Some(range) if if_range_matches(req.headers(), &etag, modified) => parse_range(range, len),
+        _ => None,
+    };
+
+    let (status, sections, body_len) = match ranges {
+        None => {
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            (StatusCode::OK, vec![Section::File(0, len)], len)
+        }
+        Some(ranges) if ranges.is_empty() => {
+            let content_range =
+                HeaderValue::from_str(&format!("bytes */{}", len)).map_err(|err| {
+                    http_error_internal_server_error!("invalid header value: {}", err)
+                })?;
+            return Err(http_error_range_not_satisfiable!(
+                "range not satisfiable for a length of {} bytes",
+                len
+            )
+            .with_header(header::CONTENT_RANGE, content_range));
+        }
+        Some(ranges) if ranges.len() == 1 => {
+            let (start, end) = ranges[0];
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, len)).map_err(
+                    |err| http_error_internal_server_error!("invalid header value: {}", err),
+                )?,
+            );
+            let section_len = end - start + 1;
+            (
+                StatusCode::PARTIAL_CONTENT,
+                vec![Section::File(start, section_len)],
+                section_len,
+            )
+        }
+        Some(ranges) => {
+            let (sections, body_len) = multipart_sections(&ranges, len, content_type);
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(&format!("multipart/byteranges; boundary={}", BOUNDARY))
+                    .map_err(|err| {
+                        http_error_internal_server_error!("invalid header value: {}", err)
+                    })?,
+            );
+            (StatusCode::PARTIAL_CONTENT, sections, body_len)
+        }
+    };
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+
+    let body = if req.method() == Method::HEAD {
+        Body::empty()
+    } else {
+        let file = File::open(path)
+            .await
+            .map_err(|err| io_error(err, req.uri().path()))?;
+        file_body(file, sections)
+    };
+
+    let mut res = Response::new(body);
+    *res.status_mut() = status;
+    *res.headers_mut() = headers;
+    Ok(res)
+}
+
+/// A part of a streamed file body.
+pub(crate) enum Section {
+    /// Raw bytes (e.g. multipart headers).
+    Bytes(Bytes),
+    /// A file section given by its start offset and length.
+    File(u64, u64),
+}
+
+struct FileBodyState {
+    file: File,
+    sections: VecDeque<Section>,
+    remaining: u64,
+}
+
+/// Stream the given sections of a file without loading them into memory.
+pub(crate) fn file_body(file: File, sections: Vec<Section>) -> Body {
+    let state = FileBodyState {
+        file,
+        sections: sections.into(),
+        remaining: 0,
+    };
+    let stream = stream::unfold(state, |mut state| async move {
+        loop {
+            if state.remaining > 0 {
+                let size = CHUNK_SIZE.min(state.remaining as usize);
+                let mut buf = vec![0; size];
+                return match state.file.read(&mut buf).await {
+                    Ok(0) => {
+                        state.remaining = 0;
+                        state.sections.clear();
+                        let err = io::Error::new(ErrorKind::UnexpectedEof, "file was truncated");
+                        Some((Err(err), state))
+                    }
+                    Ok(n) => {
+                        buf.truncate(n);
+                        state.remaining -= n as u64;
+                        Some((Ok(Bytes::from(buf)), state))
+                    }
+                    Err(err) => {
+                        state.remaining = 0;
+                        state.sections.clear();
+                        Some((Err(err), state))
+                    }
+                };
+            }
+
+            match state.sections.pop_front()? {
+                Section::Bytes(bytes) => return Some((Ok(bytes), state)),
+                Section::File(start, len) => {
+                    if let Err(err) = state.file.seek(SeekFrom::Start(start)).await {
+                        state.sections.clear();
+                        return Some((Err(err), state));
+                    }
+                    state.remaining = len;
+                }
+            }
+        }
+    });
+    Body::wrap_stream(stream)
+}
+
+// Build the sections of a `multipart/byteranges` body and compute its length.
+fn multipart_sections(ranges: &[(u64, u64)], len: u64, content_type: &str) -> (Vec<Section>, u64) {
+    let mut sections = vec![];
+    let mut body_len = 0;
+    for (i, (start, end)) in ranges.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "\r\n" };
+        let part_header = format!(
+            "{}--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+            separator, BOUNDARY, content_type, start, end, len
+        );
+        body_len += part_header.len() as u64 + (end - start + 1);
+        sections.push(Section::Bytes(Bytes::from(part_header)));
+        sections.push(Section::File(*start, end - start + 1));
+    }
+    let closing = format!("\r\n--{}--\r\n", BOUNDARY);
+    body_len += closing.len() as u64;
+    sections.push(Section::Bytes(Bytes::from(closing)));
+    (sections, body_len)
+}
+
+/// Parse a `Range` header value into a list of inclusive byte ranges.
+///
+/// Returns `None` if the header should be ignored (invalid syntax, other units or too many ranges)
+/// and an empty list if none of the ranges can be satisfied.
+fn parse_range(value: &HeaderValue, len: u64) -> Option<Vec<(u64, u64)>> {
+    let value = value.to_str().ok()?.trim();
+    let specs = value.strip_prefix("bytes=")?;
+
+    let mut ranges = vec![];
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (start, end) = spec.split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+        let range = if start.is_empty() {
+            // Suffix range, e.g. `-500`
+            let suffix = end.parse::<u64>().ok()?;
+            if suffix == 0 || len == 0 {
+                None
+            } else {
+                Some((len.saturating_sub(suffix), len - 1))
+            }
+        } else {
+            let start = start.parse::<u64>().ok()?;
+            let end = match end {
+                "" => u64::MAX,
+                end => end.parse::<u64>().ok()?,
+            };
+            if end < start {
+                return None;
+            }
+            if start >= len {
+                None
+            } else {
+                Some((start, end.min(len - 1)))
+            }
+        };
+        ranges.extend(range);
+    }
+
+    if ranges.len() > MAX_RANGES {
+        return None;
+    }
+    Some(ranges)
+}
+
+// Build a strong entity tag from the file length, modification time and content coding.
+fn entity_tag(len: u64, modified: Option<SystemTime>, encoding: Option<Encoding>) -> String {
+    let modified = modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let suffix = encoding
+        .map(|encoding| format!("-{}", encoding.as_str()))
+        .unwrap_or_default();
+    format!(
+        "\"{:x}-{:x}.{:x}{}\"",
+        len,
+        modified.as_secs(),
+        modified.subsec_nanos(),
+        suffix
+    )
+}
+
+// Returns `true` if the conditional request headers allow a `304 Not Modified` response.
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        // `If-None-Match` takes precedence over `If-Modified-Since`
+        return if_none_match
+            .to_str()
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || weak_eq(tag, etag))
+            })
+            .unwrap_or(false);
+    }
+
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok());
+    match (since, modified) {
+        (Some(since), Some(modified)) => truncate_secs(modified) <= truncate_secs(since),
+        _ => false,
+    }
+}
+
+// Returns `true` if the `Range` header should be honored according to `If-Range`.
+fn if_range_matches(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    let if_range = match headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) {
+        Some(if_range) => if_range.trim(),
+        None => return true,
+    };
+    if if_range.starts_with('"') {
+        // Strong comparison
+        return if_range == etag;
+    }
+    match (httpdate::parse_http_date(if_range).ok(), modified) {
+        (Some(date), Some(modified)) => truncate_secs(date) == truncate_secs(modified),
+        _ => false,
+    }
+}
+
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+// HTTP dates have a one second resolution.
+fn truncate_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// Redirect a directory request to the same path with a trailing slash
+// so relative links resolve correctly.
+fn redirect_to_dir(req: &Request) -> Result<Response> {
+    let uri = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|original| &original.0)
+        .unwrap_or_else(|| req.uri());
+    let location = match uri.query() {
+        Some(query) => format!("{}/?{}", uri.path(), query),
+        None => format!("{}/", uri.path()),
+    };
+    let location = HeaderValue::from_str(&location).map_err(|err| {
+        http_error_internal_server_error!("invalid redirect location `{}`: {}", location, err)
+    })?;
+    Ok(hyper::Response::builder()
+        .status(StatusCode::MOVED_PERMANENTLY)
+        .header(header::LOCATION, location)
+        .body(Body::empty())?)
+}
+
+// Render an HTML listing of a directory.
+async fn render_listing(req: &Request, dir: &Path) -> Result<Response> {
+    let path = req.uri().path();
+    let mut entries = vec![];
+    let mut read_dir = tokio::fs::read_dir(dir)
+        .await
+        .map_err(|err| io_error(err, path))?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let is_dir = entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false);
+        entries.push((name, is_dir));
+    }
+    // Directories first, then by name
+    entries.sort_by(|(a, a_dir), (b, b_dir)| b_dir.cmp(a_dir).then_with(|| a.cmp(b)));
+
+    let title = html_escape(&percent_decode(path));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index of {0}</title>\n</head>\n<body>\n<h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    if path != "/" {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (name, is_dir) in entries {
+        let slash = if is_dir { "/" } else { "" };
+        let _ = writeln!(
+            html,
+            "<li><a href=\"{}{}\">{}{}</a></li>",
+            percent_encode(&name),
+            slash,
+            html_escape(&name),
+            slash
+        );
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+
+    let body = if req.method() == Method::HEAD {
+        Body::empty()
+    } else {
+        Body::from(html.clone())
+    };
+    Ok(hyper::Response::builder()
+        .header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        )
+        .header(header::CONTENT_LENGTH, HeaderValue::from(html.len()))
+        .body(body)?)
+}
+
+fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+// Map an IO error to an HTTP error.
+fn io_error(err: io::Error, path: &str) -> Error {
+    match err.kind() {
+        ErrorKind::PermissionDenied => {
+            http_error_forbidden!("path `{}` cannot be accessed", path)
+        }
+        // Other errors come from paths which can't exist (e.g. a file used as a directory
+        // or an invalid file name) so they are reported as not found.
+        _ => http_error_not_found!("path `{}` not found: {}", path, err),
+    }
+}
@@ -0,0 +1,207 @@
+//! The request timeout module.
+//!
+//! It provides a [`Timeout`] middleware which bounds the total time spent handling a request.
+//! It's an [`AroundMiddleware`] so it wraps the current `Handler` of a [`Middlewares`][`super::Middlewares`] chain,
+//! that is, the time spent in its `BeforeMiddleware` and `AfterMiddleware` is not accounted.
+//!
+//! When a request takes longer than allowed, the handler future is dropped and a
+//! `504 Gateway Timeout` error (or `503 Service Unavailable` if configured) is returned instead.
+//!
+//! Durations can be configured per route (path prefix) and/or method. The most specific rule wins,
+//! that is, the one with the longest matching prefix and then the one matching the method.
+//!
+//! Timers are created via the [`Timer`] trait which defaults to the Tokio timer ([`TokioTimer`]),
+//! so other runtimes can be plugged in via [`Timeout::with_timer`].
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper::Method;
+//! use hyper_middleware::{Body, Middlewares, Request, Response, Result, Timeout};
+//! use std::time::Duration;
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() -> Result {
+//!     let handler = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::empty())) };
+//!
+//!     let timeout = Timeout::new(Duration::from_secs(10))
+//!         .route("/uploads", Duration::from_secs(60))
+//!         .method(Method::GET, Duration::from_secs(5));
+//!
+//!     let mut middlewares = Middlewares::new(handler);
+//!     middlewares.link_around(timeout).await;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use async_trait::async_trait;
+use hyper::{Method, StatusCode};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::future::race;
+use crate::middleware::{AroundMiddleware, Handler};
+use crate::{http_error_gateway_timeout, http_error_service_unavailable};
+use crate::{Error, Request, Response, Result};
+
+/// Defines a way to create timers so the [`Timeout`] middleware is not tied to a specific runtime.
+pub trait Timer: Send + Sync + 'static {
+    /// Returns a future which resolves once the given duration elapsed.
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// A [`Timer`] backed by the Tokio runtime.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioTimer;
+
+impl Timer for TokioTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    prefix: Option<String>,
+    method: Option<Method>,
+    duration: Duration,
+}
+
+impl Rule {
+    // Returns the specificity of the rule if it applies to the given method and path.
+    fn matches(&self, method: &Method, path: &str) -> Option<(usize, bool)> {
+        if let Some(m) = &self.method {
+            if m != method {
+                return None;
+            }
+        }
+        let prefix_len = match &self.prefix {
+            Some(prefix) if has_prefix(path, prefix) => prefix.len(),
+            Some(_) => return None,
+            None => 0,
+        };
+        Some((prefix_len, self.method.is_some()))
+    }
+}
+
+/// An [`AroundMiddleware`] which bounds the time spent handling a request.
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Clone)]
+pub struct Timeout {
+    default: Duration,
+    rules: Vec<Rule>,
+    service_unavailable: bool,
+    timer: Arc<dyn Timer>,
+}
+
+impl Timeout {
+    /// Create a new middleware using the given duration for every request.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            default: duration,
+            rules: vec![],
+            service_unavailable: false,
+            timer: Arc::new(TokioTimer),
+        }
+    }
+
+    /// Use the given duration for requests whose path starts with the given prefix.
+    pub fn route(self, prefix: &str, duration: Duration) -> Self {
+        self.rule(Some(prefix), None, duration)
+    }
+
+    /// Use the given duration for requests with the given method.
+    pub fn method(self, method: Method, duration: Duration) -> Self {
+        self.rule(None, Some(method), duration)
+    }
+
+    /// Use the given duration for requests with the given method whose path starts with the given prefix.
+    pub fn route_method(self, prefix: &str, method: Method, duration: Duration) -> Self {
+        self.rule(Some(prefix), Some(method), duration)
+    }
+
+    /// Respond with `503 Service Unavailable` instead of `504 Gateway Timeout` when a request times out.
+    pub fn with_service_unavailable(mut self) -> Self {
+        self.service_unavailable = true;
+        self
+    }
+
+    /// Set the [`Timer`] used to create the timeouts. Defaults to [`TokioTimer`].
+    pub fn with_timer<T>(mut self, timer: T) -> Self
+    where
+        T: Timer,
+    {
+        self.timer = Arc::new(timer);
+        self
+    }
+
+    fn rule(mut self, prefix: Option<&str>, method: Option<Method>, duration: Duration) -> Self {
+        let prefix = prefix.map(|p| p.trim_end_matches('/').to_owned());
+        self.rules.push(Rule {
+            prefix,
+            method,
+            duration,
+        });
+        self
+    }
+
+    fn duration(&self, method: &Method, path: &str) -> Duration {
+        self.rules
+            .iter()
+            .filter_map(|rule| rule.matches(method, path).map(|rank| (rank, rule)))
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, rule)| rule.duration)
+            .unwrap_or(self.default)
+    }
+
+    fn expired(&self, duration: Duration) -> Error {
+        if self.service_unavailable {
+            http_error_service_unavailable!("request timed out after {:?}", duration)
+        } else {
+            http_error_gateway_timeout!("request timed out after {:?}", duration)
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl AroundMiddleware for Timeout {
+    async fn around(self, handler: Box<dyn Handler>) -> Box<dyn Handler> {
+        Box::new(TimeoutHandler {
+            handler,
+            timeout: self,
+        })
+    }
+}
+
+struct TimeoutHandler {
+    handler: Box<dyn Handler>,
+    timeout: Timeout,
+}
+
+#[async_trait]
+impl Handler for TimeoutHandler {
+    async fn handle(&self, req: &mut Request) -> Result<Response> {
+        let duration = self.timeout.duration(req.method(), req.uri().path());
+        let elapsed = async {
+            self.timeout.timer.sleep(duration).await;
+            Err(self.timeout.expired(duration))
+        };
+        race(self.handler.handle(req), elapsed).await
+    }
+
+    fn name(&self) -> &'static str {
+        self.handler.name()
+    }
+}
+
+// Checks whether a path starts with the given prefix on a segment boundary.
+fn has_prefix(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.is_empty(),
+        None => false,
+    }
+}
@@ -0,0 +1,790 @@
+//! The TLS module (`rustls` feature).
+//!
+//! It provides TLS support powered by [rustls](https://github.com/rustls/rustls):
+//!
+//! - [`TlsConfig`] configures the certificates, ALPN protocols and handshake options.
+//! - [`TlsAcceptor`] wraps a Hyper [`Accept`] (e.g. [`AddrIncoming`][`hyper::server::conn::AddrIncoming`]
+//!   or a [`ProxyProtocolAcceptor`][`super::ProxyProtocolAcceptor`]) and performs the TLS handshake
+//!   of every accepted connection.
+//! - [`TlsStream`] wraps the connection stream and implements [`RemoteAddr`] and [`ConnectionInfo`]
+//!   so the [`Service`][`super::Service`] keeps reporting the client address.
+//!
+//! Every request gets a [`TlsInfo`] value in its extensions with the negotiated
+//! protocol version, cipher suite, ALPN protocol and SNI server name.
+//!
+//! ## Certificates
+//!
+//! Certificates and private keys are loaded from PEM files. Several certificates can be
+//! configured and chosen by the SNI server name sent by the client (wildcard names like `*.example.com`
+//! are supported), falling back to the default certificate if any.
+//!
+//! When a reload interval is set, the files are checked for changes periodically by a background task
+//! and the certificates are reloaded without restarting the server. A [`TlsReloader`] also allows to reload them on demand
+//! (e.g. on `SIGHUP`). If a reload fails, the previous certificates are kept.
+//!
+//! ## Client certificates
+//!
+//! Mutual TLS is enabled via [`TlsConfig::client_auth`]: client certificates are verified against
+//! the given CA certificates and the verified chain is inserted into the request extensions as
+//! [`PeerCertificates`][`super::PeerCertificates`]. See the [`client_cert`][`super::client_cert`] module
+//! for a middleware which authorizes requests by client certificate.
+//!
+//! ## ALPN
+//!
+//! Only the `http/1.1` protocol is advertised by default since this crate doesn't enable the `http2`
+//! Hyper feature. Enable it and advertise `h2` via [`TlsConfig::alpn_protocols`] to serve HTTP/2 connections.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use hyper::server::conn::AddrIncoming;
+//! use hyper::Server;
+//! use hyper_middleware::{Body, Request, Response, Result, Service, TlsConfig, TlsInfo};
+//! use std::time::Duration;
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() -> Result {
+//!     let handler = |req: &mut Request| -> Result<Response> {
+//!         let tls = req.extensions().get::<TlsInfo>().unwrap();
+//!         Ok(Response::new(Body::from(format!("¡Hola! via {:?}", tls.version))))
+//!     };
+//!
+//!     let addr = ([127, 0, 0, 1], 8443).into();
+//!     let incoming = AddrIncoming::bind(&addr)?;
+//!     let acceptor = TlsConfig::new()
+//!         .certificate("certs/default.pem", "certs/default.key")
+//!         .sni_certificate("api.example.com", "certs/api.pem", "certs/api.key")
+//!         .reload_interval(Duration::from_secs(60))
+//!         .acceptor(incoming)?;
+//!
+//!     let server = Server::builder(acceptor).serve(Service::new(handler));
+//!
+//!     println!("Listening on https://{}", addr);
+//!
+//!     server.await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use hyper::server::accept::Accept;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{crypto, Error as RustlsError, InconsistentKeys};
+use tokio_rustls::rustls::{RootCertStore, ServerConfig, ServerConnection};
+
+use crate::client_cert::PeerCertificates;
+use crate::connection::{ConnectionExtensions, ConnectionInfo};
+use crate::future::Handshakes;
+use crate::remote_addr::RemoteAddr;
+use crate::Result;
+
+/// The TLS information of the connection a request arrived on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// The negotiated protocol version (e.g. `TLSv1_3`).
+    pub version: Option<&'static str>,
+    /// The negotiated cipher suite (e.g. `TLS13_AES_256_GCM_SHA384`).
+    pub cipher_suite: Option<&'static str>,
+    /// The negotiated ALPN protocol (e.g. `h2`), if any.
+    pub alpn_protocol: Option<String>,
+    /// The SNI server name sent by the client, if any.
+    pub server_name: Option<String>,
+}
+
+impl TlsInfo {
+    fn from_connection(conn: &ServerConnection) -> Self {
+        Self {
+            version: conn.protocol_version().and_then(|v| v.as_str()),
+            cipher_suite: conn
+                .negotiated_cipher_suite()
+                .and_then(|suite| suite.suite().as_str()),
+            alpn_protocol: conn
+                .alpn_protocol()
+                .map(|p| String::from_utf8_lossy(p).into_owned()),
+            server_name: conn.server_name().map(str::to_owned),
+        }
+    }
+}
+
+/// The TLS configuration which produces a [`TlsAcceptor`].
+///
+/// See the [module documentation][`self`] for more details.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    sources: Vec<CertSource>,
+    alpn_protocols: Vec<Vec<u8>>,
+    reload_interval: Option<Duration>,
+    handshake_timeout: Duration,
+    client_auth: Option<(PathBuf, ClientAuth)>,
+}
+
+/// Defines whether clients must present a certificate. See [`TlsConfig::client_auth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuth {
+    /// Clients may connect without a certificate, but a presented one must be valid.
+    Optional,
+    /// Clients must present a valid certificate, otherwise the handshake fails.
+    Required,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TlsConfig {
+    /// Create a new configuration which advertises the `http/1.1` ALPN protocol
+    /// and has a handshake timeout of 10 seconds.
+    pub fn new() -> Self {
+        Self {
+            sources: vec![],
+            alpn_protocols: vec![b"http/1.1".to_vec()],
+            reload_interval: None,
+            handshake_timeout: Duration::from_secs(10),
+            client_auth: None,
+        }
+    }
+
+    /// Set the default certificate chain and private key PEM files,
+    /// used when no SNI certificate matches.
+    pub fn certificate<C, K>(mut self, cert: C, key: K) -> Self
+    where
+        C: Into<PathBuf>,
+        K: Into<PathBuf>,
+    {
+        self.sources.retain(|source| source.server_name.is_some());
+        self.sources
+            .push(CertSource::new(None, cert.into(), key.into()));
+        self
+    }
+
+    /// Add a certificate chain and private key PEM files for the given SNI server name.
+    ///
+    /// The server name may be a wildcard like `*.example.com` which matches a single label.
+    pub fn sni_certificate<C, K>(mut self, server_name: &str, cert: C, key: K) -> Self
+    where
+        C: Into<PathBuf>,
+        K: Into<PathBuf>,
+    {
+        let server_name = server_name.trim_end_matches('.').to_ascii_lowercase();
+        self.sources
+            .retain(|source| source.server_name.as_deref() != Some(server_name.as_str()));
+        self.sources
+            .push(CertSource::new(Some(server_name), cert.into(), key.into()));
+        self
+    }
+
+    /// Set the ALPN protocols advertised to clients, in order of preference.
+    ///
+    /// Advertising `h2` requires the `http2` Hyper feature to be enabled.
+    pub fn alpn_protocols<I, S>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.alpn_protocols = protocols
+            .into_iter()
+            .map(|p| p.as_ref().as_bytes().to_vec())
+            .collect();
+        self
+    }
+
+    /// Check the certificate files for changes periodically and reload them.
+    pub fn reload_interval(mut self, interval: Duration) -> Self {
+        self.reload_interval = Some(interval);
+        self
+    }
+
+    /// Verify client certificates against the CA certificates of the given PEM file.
+    pub fn client_auth<P>(mut self, ca: P, mode: ClientAuth) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.client_auth = Some((ca.into(), mode));
+        self
+    }
+
+    /// Set the maximum time to complete the TLS handshake of a connection.
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Load the certificates and wrap a Hyper [`Accept`] so every accepted connection is TLS terminated.
+    ///
+    /// It returns an error if no certificate was configured or a certificate can't be loaded.
+    ///
+    /// The periodic reload is spawned as a task, so it requires to be called within a Tokio runtime
+    /// with the time driver enabled. The task stops once the acceptor and its reloaders are dropped.
+    pub fn acceptor<I>(self, incoming: I) -> Result<TlsAcceptor<I>>
+    where
+        I: Accept,
+    {
+        if self.sources.is_empty() {
+            return Err(crate::error!("no TLS certificate configured"));
+        }
+        let runtime = match self.reload_interval {
+            Some(_) => Some(
+                tokio::runtime::Handle::try_current()
+                    .map_err(|err| crate::error!("unable to spawn the TLS reload task: {}", err))?,
+            ),
+            None => None,
+        };
+        let resolver = Arc::new(CertResolver {
+            certs: RwLock::new(Arc::new(Certs::load(&self.sources)?)),
+            sources: self.sources,
+        });
+
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|err| crate::error!("invalid TLS configuration: {}", err))?;
+        let builder = match &self.client_auth {
+            Some((ca, mode)) => {
+                let mut roots = RootCertStore::empty();
+                for cert in rustls_pemfile::certs(&mut open(ca)?) {
+                    let cert = cert.map_err(|err| invalid_file(ca, err))?;
+                    roots.add(cert).map_err(|err| invalid_file(ca, err))?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = match mode {
+                    ClientAuth::Optional => verifier.allow_unauthenticated(),
+                    ClientAuth::Required => verifier,
+                };
+                let verifier = verifier.build().map_err(|err| invalid_file(ca, err))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_cert_resolver(resolver.clone());
+        config.alpn_protocols = self.alpn_protocols;
+
+        if let (Some(runtime), Some(interval)) = (runtime, self.reload_interval) {
+            runtime.spawn(reload_periodically(Arc::downgrade(&resolver), interval));
+        }
+
+        Ok(TlsAcceptor {
+            acceptor: tokio_rustls::TlsAcceptor::from(Arc::new(config)),
+            resolver,
+            handshake_timeout: self.handshake_timeout,
+            handshakes: Handshakes::new(incoming),
+        })
+    }
+}
+
+// Check the certificate files for changes on every tick, off the async workers since it blocks on file I/O.
+async fn reload_periodically(resolver: Weak<CertResolver>, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let resolver = match resolver.upgrade() {
+            Some(resolver) => resolver,
+            None => return,
+        };
+        match tokio::task::spawn_blocking(move || resolver.reload_if_changed()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_err)) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_err, "unable to reload the TLS certificates");
+            }
+            // The blocking task panicked or the runtime is shutting down
+            Err(_) => return,
+        }
+    }
+}
+
+/// A handle to reload the certificates of a [`TlsAcceptor`] on demand.
+#[derive(Debug, Clone)]
+pub struct TlsReloader {
+    resolver: Arc<CertResolver>,
+}
+
+impl TlsReloader {
+    /// Reload all the certificates from disk.
+    ///
+    /// If any certificate can't be loaded, an error is returned and the previous certificates are kept.
+    pub fn reload(&self) -> Result {
+        self.resolver.reload()
+    }
+}
+
+/// A Hyper [`Accept`] which performs the TLS handshake of the accepted connections.
+///
+/// Handshakes are performed concurrently so slow clients don't hold back the other ones.
+/// Connections whose handshake fails or times out are closed.
+pub struct TlsAcceptor<I>
+where
+    I: Accept,
+{
+    acceptor: tokio_rustls::TlsAcceptor,
+    resolver: Arc<CertResolver>,
+    handshake_timeout: Duration,
+    handshakes: Handshakes<I, TlsStream<I::Conn>>,
+}
+
+impl<I> TlsAcceptor<I>
+where
+    I: Accept,
+{
+    /// Returns a handle to reload the certificates on demand.
+    pub fn reloader(&self) -> TlsReloader {
+        TlsReloader {
+            resolver: self.resolver.clone(),
+        }
+    }
+
+    /// Returns a reference to the inner acceptor.
+    pub fn get_ref(&self) -> &I {
+        self.handshakes.get_ref()
+    }
+}
+
+impl<I> Accept for TlsAcceptor<I>
+where
+    I: Accept + Unpin,
+    I::Conn: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Conn = TlsStream<I::Conn>;
+    type Error = I::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let this = self.get_mut();
+        let acceptor = &this.acceptor;
+        let timeout = this.handshake_timeout;
+        this.handshakes.poll_accept(cx, "TLS handshake", |conn| {
+            let accept = acceptor.accept(conn);
+            Box::pin(async move {
+                match tokio::time::timeout(timeout, accept).await {
+                    Ok(Ok(inner)) => Ok(TlsStream::new(inner)),
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "timed out performing the TLS handshake",
+                    )),
+                }
+            })
+        })
+    }
+}
+
+/// A TLS terminated connection stream.
+pub struct TlsStream<S> {
+    inner: tokio_rustls::server::TlsStream<S>,
+    info: TlsInfo,
+    peer_certificates: Option<PeerCertificates>,
+}
+
+impl<S> TlsStream<S> {
+    fn new(inner: tokio_rustls::server::TlsStream<S>) -> Self {
+        let conn = inner.get_ref().1;
+        let info = TlsInfo::from_connection(conn);
+        let peer_certificates = conn
+            .peer_certificates()
+            .filter(|chain| !chain.is_empty())
+            .map(PeerCertificates::from_chain);
+        Self {
+            inner,
+            info,
+            peer_certificates,
+        }
+    }
+
+    /// Returns the TLS information of the connection.
+    pub fn info(&self) -> &TlsInfo {
+        &self.info
+    }
+
+    /// Returns the verified client certificate chain of the connection, if any.
+    pub fn peer_certificates(&self) -> Option<&PeerCertificates> {
+        self.peer_certificates.as_ref()
+    }
+
+    /// Returns a reference to the underlying stream and the rustls connection.
+    pub fn get_ref(&self) -> (&S, &ServerConnection) {
+        self.inner.get_ref()
+    }
+}
+
+impl<S> fmt::Debug for TlsStream<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsStream")
+            .field("inner", self.inner.get_ref().0)
+            .field("info", &self.info)
+            .finish()
+    }
+}
+
+impl<S> RemoteAddr for TlsStream<S>
+where
+    S: RemoteAddr,
+{
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.inner.get_ref().0.remote_addr()
+    }
+}
+
+impl<S> ConnectionInfo for TlsStream<S>
+where
+    S: ConnectionInfo,
+{
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.inner.get_ref().0.local_addr()
+    }
+
+    fn connection_id(&self) -> Option<u64> {
+        self.inner.get_ref().0.connection_id()
+    }
+
+    fn accepted_at(&self) -> Option<SystemTime> {
+        self.inner.get_ref().0.accepted_at()
+    }
+
+    fn extensions(&self) -> ConnectionExtensions {
+        let mut extensions = self.inner.get_ref().0.extensions();
+        extensions.insert(self.info.clone());
+        if let Some(peer_certificates) = &self.peer_certificates {
+            extensions.insert(peer_certificates.clone());
+        }
+        extensions
+    }
+}
+
+impl<S> AsyncRead for TlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl<S> AsyncWrite for TlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+// The certificate and private key files of a server name.
+#[derive(Debug)]
+struct CertSource {
+    server_name: Option<String>,
+    cert: PathBuf,
+    key: PathBuf,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl Clone for CertSource {
+    fn clone(&self) -> Self {
+        Self::new(
+            self.server_name.clone(),
+            self.cert.clone(),
+            self.key.clone(),
+        )
+    }
+}
+
+impl CertSource {
+    fn new(server_name: Option<String>, cert: PathBuf, key: PathBuf) -> Self {
+        Self {
+            server_name,
+            cert,
+            key,
+            modified: Mutex::new(None),
+        }
+    }
+
+    // Returns the latest modification time of both files.
+    fn last_modified(&self) -> Option<SystemTime> {
+        let cert = std::fs::metadata(&self.cert)
+            .and_then(|m| m.modified())
+            .ok();
+        let key = std::fs::metadata(&self.key).and_then(|m| m.modified()).ok();
+        cert.max(key)
+    }
+
+    fn load(&self) -> Result<CertifiedKey> {
+        let modified = self.last_modified();
+
+        let certs = rustls_pemfile::certs(&mut open(&self.cert)?)
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|err| invalid_file(&self.cert, err))?;
+        if certs.is_empty() {
+            return Err(invalid_file(&self.cert, "no certificate found"));
+        }
+        let key = rustls_pemfile::private_key(&mut open(&self.key)?)
+            .map_err(|err| invalid_file(&self.key, err))?
+            .ok_or_else(|| invalid_file(&self.key, "no private key found"))?;
+        let key = crypto::ring::sign::any_supported_type(&key)
+            .map_err(|err| invalid_file(&self.key, err))?;
+
+        let certified = CertifiedKey::new(certs, key);
+        match certified.keys_match() {
+            // Keys whose public key can't be extracted are accepted like rustls does
+            Ok(()) | Err(RustlsError::InconsistentKeys(InconsistentKeys::Unknown)) => {}
+            Err(err) => return Err(invalid_file(&self.key, err)),
+        }
+
+        if let Ok(mut last) = self.modified.lock() {
+            *last = modified;
+        }
+        Ok(certified)
+    }
+
+    fn is_modified(&self) -> bool {
+        let modified = self.last_modified();
+        match self.modified.lock() {
+            Ok(last) => *last != modified,
+            Err(_) => true,
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| invalid_file(path, err))
+}
+
+fn invalid_file(path: &Path, err: impl fmt::Display) -> crate::Error {
+    crate::error!("unable to load `{}`: {}", path.display(), err)
+}
+
+// The loaded certificates.
+#[derive(Debug, Default)]
+struct Certs {
+    default: Option<Arc<CertifiedKey>>,
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl Certs {
+    fn load(sources: &[CertSource]) -> Result<Self> {
+        let mut certs = Certs::default();
+        for source in sources {
+            let key = Arc::new(source.load()?);
+            match &source.server_name {
+                Some(name) => {
+                    certs.by_name.insert(name.clone(), key);
+                }
+                None => certs.default = Some(key),
+            }
+        }
+        Ok(certs)
+    }
+
+    fn resolve(&self, server_name: Option<&str>) -> Option<Arc<CertifiedKey>> {
+        if let Some(name) = server_name {
+            let name = name.trim_end_matches('.').to_ascii_lowercase();
+            if let Some(key) = self.by_name.get(&name) {
+                return Some(key.clone());
+            }
+            if let Some((_, parent)) = name.split_once('.') {
+                if let Some(key) = self.by_name.get(&format!("*.{}", parent)) {
+                    return Some(key.clone());
+                }
+            }
+        }
+        self.default.clone()
+    }
+}
+
+// Resolves the certificate of a handshake by SNI server name.
+#[derive(Debug)]
+struct CertResolver {
+    sources: Vec<CertSource>,
+    certs: RwLock<Arc<Certs>>,
+}
+
+impl CertResolver {
+    fn reload(&self) -> Result {
+        let certs = Arc::new(Certs::load(&self.sources)?);
+        if let Ok(mut current) = self.certs.write() {
+            *current = certs;
+        }
+        Ok(())
+    }
+
+    fn reload_if_changed(&self) -> Result {
+        if self.sources.iter().any(CertSource::is_modified) {
+            self.reload()?;
+        }
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certs = self.certs.read().ok()?.clone();
+        certs.resolve(client_hello.server_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/certs")
+            .join(name)
+    }
+
+    fn source(server_name: Option<&str>, name: &str) -> CertSource {
+        CertSource::new(
+            server_name.map(str::to_owned),
+            fixture(&format!("{}.pem", name)),
+            fixture(&format!("{}.key", name)),
+        )
+    }
+
+    // Returns the DER of the leaf certificate of a fixture.
+    fn leaf(name: &str) -> Vec<u8> {
+        let cert = rustls_pemfile::certs(&mut open(&fixture(&format!("{}.pem", name))).unwrap())
+            .next()
+            .unwrap()
+            .unwrap();
+        cert.to_vec()
+    }
+
+    fn resolved(certs: &Certs, server_name: Option<&str>) -> Option<Vec<u8>> {
+        certs.resolve(server_name).map(|key| key.cert[0].to_vec())
+    }
+
+    #[test]
+    fn wildcards_match_a_single_label() {
+        let certs = Certs::load(&[
+            source(None, "default"),
+            source(Some("*.example.com"), "wildcard"),
+            source(Some("example.com"), "default"),
+        ])
+        .unwrap();
+
+        let default = Some(leaf("default"));
+        let wildcard = Some(leaf("wildcard"));
+        assert_eq!(resolved(&certs, Some("a.example.com")), wildcard);
+        assert_eq!(resolved(&certs, Some("A.Example.COM.")), wildcard);
+        assert_eq!(resolved(&certs, Some("example.com")), default);
+        assert_eq!(resolved(&certs, Some("a.b.example.com")), default);
+        assert_eq!(resolved(&certs, Some("other.net")), default);
+        assert_eq!(resolved(&certs, None), default);
+
+        // Without a default certificate, unknown names get no certificate
+        let certs = Certs::load(&[source(Some("*.example.com"), "wildcard")]).unwrap();
+        assert_eq!(resolved(&certs, Some("a.b.example.com")), None);
+        assert_eq!(resolved(&certs, None), None);
+    }
+
+    #[test]
+    fn mismatched_keys_are_rejected() {
+        let source = CertSource::new(None, fixture("default.pem"), fixture("wildcard.key"));
+        let err = source.load().unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("wildcard.key") && message.contains("KeyMismatch"),
+            "{}",
+            message
+        );
+        assert_eq!(err.status(), None);
+
+        let source = CertSource::new(None, fixture("default.pem"), fixture("missing.key"));
+        assert!(source.load().is_err());
+    }
+
+    #[test]
+    fn certificates_are_reloaded_once_changed() {
+        let dir = std::env::temp_dir().join(format!(
+            "hyper-middleware-tls-reload-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+        let install = |name: &str, modified: SystemTime| {
+            for (from, to) in [("pem", &cert), ("key", &key)] {
+                std::fs::copy(fixture(&format!("{}.{}", name, from)), to).unwrap();
+                File::options()
+                    .write(true)
+                    .open(to)
+                    .unwrap()
+                    .set_modified(modified)
+                    .unwrap();
+            }
+        };
+        let epoch = SystemTime::UNIX_EPOCH;
+        install("default", epoch + Duration::from_secs(1));
+
+        let sources = vec![CertSource::new(None, cert.clone(), key.clone())];
+        let resolver = CertResolver {
+            certs: RwLock::new(Arc::new(Certs::load(&sources).unwrap())),
+            sources,
+        };
+        let current = || resolver.certs.read().unwrap().clone();
+
+        // Unchanged files are not loaded again
+        let before = current();
+        resolver.reload_if_changed().unwrap();
+        assert!(Arc::ptr_eq(&before, &current()));
+
+        install("wildcard", epoch + Duration::from_secs(2));
+        resolver.reload_if_changed().unwrap();
+        assert_eq!(resolved(&current(), None), Some(leaf("wildcard")));
+
+        // Invalid files keep the current certificates
+        std::fs::write(&key, "invalid").unwrap();
+        assert!(resolver.reload_if_changed().is_err());
+        assert_eq!(resolved(&current(), None), Some(leaf("wildcard")));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,148 @@
+//! Internal `tracing` instrumentation of the request handling flow.
+//!
+//! Everything here compiles down to plain `await`s when the `tracing` feature is disabled.
+
+use std::future::Future;
+
+#[cfg(feature = "tracing")]
+use crate::middleware::BeforeOutcome;
+#[cfg(feature = "tracing")]
+use crate::Request;
+use crate::{Response, Result};
+
+/// The phase of the middleware chain a traced call belongs to.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Phase {
+    Before,
+    Handler,
+    After,
+    Catch,
+}
+
+#[cfg(feature = "tracing")]
+impl Phase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Before => "before",
+            Phase::Handler => "handler",
+            Phase::After => "after",
+            Phase::Catch => "catch",
+        }
+    }
+}
+
+/// Describes the successful outcome of a traced call.
+#[cfg(feature = "tracing")]
+pub(crate) trait Outcome {
+    /// Returns the outcome recorded in the span.
+    fn outcome(&self) -> &'static str {
+        "ok"
+    }
+
+    /// Returns the response status recorded in the span, if any.
+    fn status(&self) -> Option<u16> {
+        None
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for () {}
+
+#[cfg(feature = "tracing")]
+impl Outcome for Response {
+    fn status(&self) -> Option<u16> {
+        Some(self.status().as_u16())
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Outcome for BeforeOutcome {
+    fn outcome(&self) -> &'static str {
+        match self {
+            BeforeOutcome::Continue => "continue",
+            BeforeOutcome::Respond(_) => "respond",
+        }
+    }
+
+    fn status(&self) -> Option<u16> {
+        match self {
+            BeforeOutcome::Continue => None,
+            BeforeOutcome::Respond(res) => Some(res.status().as_u16()),
+        }
+    }
+}
+
+/// Run a middleware or handler call inside of a child span of the current request span
+/// which records the middleware name and the outcome of the call.
+#[cfg(feature = "tracing")]
+pub(crate) async fn traced<F, T>(phase: Phase, name: &'static str, fut: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+    T: Outcome,
+{
+    use tracing::field::Empty;
+    use tracing::Instrument;
+
+    let span = tracing::debug_span!(
+        "middleware",
+        phase = phase.as_str(),
+        name,
+        outcome = Empty,
+        status = Empty,
+        error = Empty,
+    );
+    let result = fut.instrument(span.clone()).await;
+    match &result {
+        Ok(value) => {
+            let outcome = match phase {
+                Phase::Catch => "recovered",
+                _ => value.outcome(),
+            };
+            span.record("outcome", outcome);
+            if let Some(status) = value.status() {
+                span.record("status", status);
+            }
+        }
+        Err(err) => {
+            span.record("outcome", "error");
+            if let Some(status) = err.status() {
+                span.record("status", status.as_u16());
+            }
+            span.record("error", tracing::field::display(err));
+        }
+    }
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) async fn traced<F, T>(_: Phase, _: &'static str, fut: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    fut.await
+}
+
+/// Create the span of an incoming request.
+#[cfg(feature = "tracing")]
+pub(crate) fn request_span(req: &Request) -> tracing::Span {
+    use tracing::field::Empty;
+
+    let remote_addr = req.extensions().get::<std::net::SocketAddr>().copied();
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        version = ?req.version(),
+        remote_addr = remote_addr.map(tracing::field::display),
+        status = Empty,
+    )
+}
+
+/// Record the final response status in the current request span.
+#[cfg(feature = "tracing")]
+pub(crate) fn record_status(res: &Response) {
+    tracing::Span::current().record("status", res.status().as_u16());
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn record_status(_: &Response) {}
@@ -0,0 +1,183 @@
+//! The Unix domain sockets module.
+//!
+//! It provides a [`UnixIncoming`] Hyper [`Accept`] implementation which allows to serve
+//! a [`Service`][`super::Service`] over a Unix domain socket.
+//!
+//! Unix socket connections have no [`SocketAddr`] so the service inserts
+//! a [`UnixPeer`] into the request extensions instead, which holds the peer credentials
+//! (user, group and process IDs) and the socket path.
+//! It's provided by the [`ConnectionInfo::extensions`] of the stream, so the
+//! [`RemoteAddr`] and [`ConnectionInfo`] traits stay the same on every platform.
+//!
+//! Like Hyper's `AddrIncoming`, the listener keeps running when the process runs out of
+//! file descriptors or memory: it stops accepting connections for a second and then retries.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use hyper::Server;
+//! use hyper_middleware::{Body, Request, Response, Result, Service, UnixIncoming, UnixPeer};
+//!
+//! #[tokio::main(flavor = "multi_thread")]
+//! async fn main() -> Result {
+//!     let handler = |req: &mut Request| -> Result<Response> {
+//!         let peer = req.extensions().get::<UnixPeer>().unwrap();
+//!         Ok(Response::new(Body::from(format!("¡Hola, {:?}!", peer.uid))))
+//!     };
+//!
+//!     let path = std::env::temp_dir().join("hyper-middleware-example.sock");
+//!     # let _ = std::fs::remove_file(&path);
+//!     let incoming = UnixIncoming::bind(&path)?;
+//!     let server = Server::builder(incoming).serve(Service::new(handler));
+//!
+//!     println!("Listening on unix:{}", path.display());
+//!
+//!     // server.await?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use futures_core::ready;
+use hyper::server::accept::Accept;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::time::Sleep;
+
+use crate::connection::{ConnectionExtensions, ConnectionInfo};
+use crate::remote_addr::RemoteAddr;
+
+/// The peer information of a Unix domain socket connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixPeer {
+    /// The user ID of the peer process, if available.
+    pub uid: Option<u32>,
+    /// The group ID of the peer process, if available.
+    pub gid: Option<u32>,
+    /// The process ID of the peer, if available on the platform.
+    pub pid: Option<i32>,
+    /// The path of the socket the connection was accepted on, if not unnamed.
+    pub path: Option<PathBuf>,
+}
+
+impl UnixPeer {
+    /// Collect the peer information of a Unix domain socket stream.
+    pub fn from_stream(stream: &UnixStream) -> Self {
+        let cred = stream.peer_cred().ok();
+        Self {
+            uid: cred.map(|cred| cred.uid()),
+            gid: cred.map(|cred| cred.gid()),
+            pid: cred.and_then(|cred| cred.pid()),
+            path: stream
+                .local_addr()
+                .ok()
+                .and_then(|addr| addr.as_pathname().map(Path::to_path_buf)),
+        }
+    }
+}
+
+impl RemoteAddr for UnixStream {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+impl ConnectionInfo for UnixStream {
+    fn extensions(&self) -> ConnectionExtensions {
+        let mut extensions = ConnectionExtensions::new();
+        extensions.insert(UnixPeer::from_stream(self));
+        extensions
+    }
+}
+
+/// A Hyper [`Accept`] which accepts connections from a Unix domain socket listener.
+#[derive(Debug)]
+pub struct UnixIncoming {
+    listener: UnixListener,
+    // The pause before accepting connections again after running out of resources
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl UnixIncoming {
+    /// Create a new Unix socket listener bound to the given path.
+    ///
+    /// The socket file must not exist.
+    pub fn bind<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::from_listener(UnixListener::bind(path)?))
+    }
+
+    /// Create a new instance from an existing Unix socket listener.
+    pub fn from_listener(listener: UnixListener) -> Self {
+        Self {
+            listener,
+            sleep: None,
+        }
+    }
+
+    /// Returns a reference to the inner listener.
+    pub fn get_ref(&self) -> &UnixListener {
+        &self.listener
+    }
+}
+
+impl Accept for UnixIncoming {
+    type Conn = UnixStream;
+    type Error = io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(sleep) = &mut this.sleep {
+            ready!(sleep.as_mut().poll(cx));
+            this.sleep = None;
+        }
+
+        loop {
+            match this.listener.poll_accept(cx) {
+                Poll::Ready(Ok((stream, _))) => return Poll::Ready(Some(Ok(stream))),
+                // Errors of a single connection shouldn't stop the server
+                Poll::Ready(Err(err)) if is_connection_error(&err) => continue,
+                // Wait for connections to be closed instead of spinning or stopping the server
+                Poll::Ready(Err(_err)) if is_resource_error(&_err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(error = %_err, "unable to accept connections, retrying in 1s");
+                    let mut sleep = Box::pin(tokio::time::sleep(Duration::from_secs(1)));
+                    if sleep.as_mut().poll(cx).is_pending() {
+                        this.sleep = Some(sleep);
+                        return Poll::Pending;
+                    }
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+// Returns `true` if the error is caused by the lack of file descriptors or memory, which is temporary.
+fn is_resource_error(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EMFILE) | Some(libc::ENFILE) | Some(libc::ENOBUFS) | Some(libc::ENOMEM)
+    )
+}
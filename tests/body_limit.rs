@@ -0,0 +1,67 @@
+use hyper::body::{to_bytes, Bytes};
+use hyper::{header, StatusCode};
+use hyper_middleware::{BeforeMiddleware, Body, BodyLimit, Error, Request};
+
+fn request(body: Body, content_length: Option<&str>) -> Request {
+    let mut builder = hyper::Request::builder().method("POST").uri("/");
+    if let Some(length) = content_length {
+        builder = builder.header(header::CONTENT_LENGTH, length);
+    }
+    builder.body(body).unwrap()
+}
+
+// A body without a known length, like a chunked upload.
+fn chunked(chunks: &[&'static str]) -> Body {
+    let chunks = chunks
+        .iter()
+        .map(|chunk| Ok::<_, std::io::Error>(Bytes::from_static(chunk.as_bytes())))
+        .collect::<Vec<_>>();
+    Body::wrap_stream(futures_util::stream::iter(chunks))
+}
+
+#[tokio::test]
+async fn content_length_over_the_limit() {
+    let limit = BodyLimit::new(10);
+
+    let mut req = request(Body::from("0123456789a"), Some("11"));
+    let err = limit.before(&mut req).await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::PAYLOAD_TOO_LARGE));
+
+    let mut req = request(Body::from("0123456789"), Some("10"));
+    limit.before(&mut req).await.unwrap();
+
+    let mut req = request(Body::from("0123"), Some("four"));
+    let err = limit.before(&mut req).await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::BAD_REQUEST));
+}
+
+#[tokio::test]
+async fn length_required() {
+    let limit = BodyLimit::new(10).require_length(true);
+
+    let mut req = request(chunked(&["0123"]), None);
+    let err = limit.before(&mut req).await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::LENGTH_REQUIRED));
+
+    // Requests without a body don't need a length
+    let mut req = request(Body::empty(), None);
+    limit.before(&mut req).await.unwrap();
+}
+
+#[tokio::test]
+async fn chunked_bodies_are_cut_off() {
+    let limit = BodyLimit::new(10);
+
+    let mut req = request(chunked(&["01234", "56789"]), None);
+    limit.before(&mut req).await.unwrap();
+    let body = to_bytes(std::mem::take(req.body_mut())).await.unwrap();
+    assert_eq!(body, "0123456789");
+
+    let mut req = request(chunked(&["01234", "56789", "a"]), None);
+    limit.before(&mut req).await.unwrap();
+    let err: Error = to_bytes(std::mem::take(req.body_mut()))
+        .await
+        .unwrap_err()
+        .into();
+    assert_eq!(err.status(), Some(StatusCode::PAYLOAD_TOO_LARGE));
+}
@@ -0,0 +1,114 @@
+#![cfg(all(feature = "gzip", feature = "deflate"))]
+
+use hyper::body::{to_bytes, Bytes};
+use hyper::header;
+use hyper_middleware::{AfterMiddleware, Body, Compression, Encoding, Request, Response};
+
+const TEXT: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ";
+
+fn request(accept_encoding: &str) -> Request {
+    hyper::Request::builder()
+        .uri("/")
+        .header(header::ACCEPT_ENCODING, accept_encoding)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn response(body: Body) -> Response {
+    hyper::Response::builder()
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(body)
+        .unwrap()
+}
+
+// Returns the `Content-Encoding` chosen for the given `Accept-Encoding` header.
+async fn negotiated(compression: &Compression, accept_encoding: &str) -> Option<String> {
+    let res = compression
+        .after(&mut request(accept_encoding), response(Body::from(TEXT)))
+        .await
+        .unwrap();
+    assert_eq!(res.headers()[header::VARY], "accept-encoding");
+    res.headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|v| v.to_str().unwrap().to_owned())
+}
+
+#[tokio::test]
+async fn q_values_are_negotiated() {
+    let compression = Compression::new()
+        .encodings([Encoding::Gzip, Encoding::Deflate])
+        .min_size(0);
+
+    for (accept_encoding, expected) in [
+        // Ties are broken by the server preference
+        ("deflate, gzip", Some("gzip")),
+        ("gzip;q=0.5, deflate", Some("deflate")),
+        ("GZIP;q=0.8, deflate;q=0.9", Some("deflate")),
+        ("*", Some("gzip")),
+        ("*;q=0.5, deflate", Some("deflate")),
+        ("gzip;q=0, *", Some("deflate")),
+        ("gzip;q=0, deflate;q=0", None),
+        ("identity", None),
+        ("br", None),
+    ] {
+        assert_eq!(
+            negotiated(&compression, accept_encoding).await.as_deref(),
+            expected,
+            "Accept-Encoding: {}",
+            accept_encoding
+        );
+    }
+}
+
+#[tokio::test]
+async fn bodies_are_compressed() {
+    let compression = Compression::new().encodings([Encoding::Gzip]).min_size(0);
+    let mut res = response(Body::from(TEXT.repeat(10)));
+    res.headers_mut()
+        .insert(header::ETAG, "\"abc\"".parse().unwrap());
+    res.headers_mut()
+        .insert(header::CONTENT_LENGTH, (TEXT.len() * 10).into());
+
+    let res = compression.after(&mut request("gzip"), res).await.unwrap();
+    assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+    assert_eq!(res.headers()[header::ETAG], "W/\"abc\"");
+    assert!(!res.headers().contains_key(header::CONTENT_LENGTH));
+    let body = to_bytes(res.into_body()).await.unwrap();
+    // The gzip magic number
+    assert_eq!(body[..2], [0x1f, 0x8b]);
+    assert!(body.len() < TEXT.len() * 10);
+}
+
+#[tokio::test]
+async fn encoded_responses_are_skipped() {
+    let compression = Compression::new().encodings([Encoding::Gzip]).min_size(0);
+    let mut res = response(Body::from("already compressed"));
+    res.headers_mut()
+        .insert(header::CONTENT_ENCODING, "br".parse().unwrap());
+
+    let res = compression.after(&mut request("gzip"), res).await.unwrap();
+    assert_eq!(res.headers()[header::CONTENT_ENCODING], "br");
+    assert!(!res.headers().contains_key(header::VARY));
+    let body = to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "already compressed");
+}
+
+#[tokio::test]
+async fn small_responses_are_skipped() {
+    let compression = Compression::new().encodings([Encoding::Gzip]);
+
+    let res = compression
+        .after(&mut request("gzip"), response(Body::from(TEXT)))
+        .await
+        .unwrap();
+    assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+
+    // Streamed bodies have an unknown size
+    let chunks = vec![Ok::<_, std::io::Error>(Bytes::from_static(TEXT.as_bytes()))];
+    let body = Body::wrap_stream(futures_util::stream::iter(chunks));
+    let res = compression
+        .after(&mut request("gzip"), response(body))
+        .await
+        .unwrap();
+    assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+}
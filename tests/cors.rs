@@ -0,0 +1,236 @@
+use hyper::{header, Method, StatusCode};
+use hyper_middleware::{
+    http_error_not_found, AfterMiddleware, BeforeMiddleware, BeforeOutcome, Body, Cors, Request,
+    Response,
+};
+use std::time::Duration;
+
+fn request(method: Method, origin: &str) -> Request {
+    hyper::Request::builder()
+        .method(method)
+        .uri("/")
+        .header(header::ORIGIN, origin)
+        .body(Body::empty())
+        .unwrap()
+}
+
+// Returns the `Access-Control-Allow-Origin` header of a simple request response.
+async fn allowed_origin(cors: &Cors, origin: &str) -> Option<String> {
+    let (_, after) = cors.clone().build();
+    let res = after
+        .after(
+            &mut request(Method::GET, origin),
+            Response::new(Body::empty()),
+        )
+        .await
+        .unwrap();
+    res.headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .map(|v| v.to_str().unwrap().to_owned())
+}
+
+#[tokio::test]
+async fn origins() {
+    let cors = Cors::new()
+        .allow_origin("https://example.com")
+        .allow_origin("https://*.example.net");
+
+    for origin in [
+        "https://example.com",
+        "https://EXAMPLE.com",
+        "https://a.example.net",
+    ] {
+        assert_eq!(allowed_origin(&cors, origin).await.as_deref(), Some(origin));
+    }
+    for origin in [
+        "https://example.com.evil.net",
+        "http://example.com",
+        "https://example.net",
+        "https://a.example.net.evil.net",
+    ] {
+        assert_eq!(allowed_origin(&cors, origin).await, None);
+    }
+
+    let cors = Cors::new().allow_any_origin();
+    assert_eq!(
+        allowed_origin(&cors, "https://any.where").await.as_deref(),
+        Some("*")
+    );
+}
+
+#[test]
+#[should_panic(expected = "credentials can't be allowed for any origin")]
+fn any_origin_with_credentials() {
+    Cors::new()
+        .allow_any_origin()
+        .allow_credentials(true)
+        .build();
+}
+
+#[cfg(feature = "regex")]
+#[tokio::test]
+async fn regex_origins_match_the_whole_origin() {
+    let cors = Cors::new()
+        .allow_origin_regex(r"https://(\w+\.)?example\.com")
+        .unwrap();
+
+    for origin in ["https://example.com", "https://a.example.com"] {
+        assert_eq!(allowed_origin(&cors, origin).await.as_deref(), Some(origin));
+    }
+    for origin in [
+        "https://example.com.evil.net",
+        "https://evil.net/?https://example.com",
+    ] {
+        assert_eq!(allowed_origin(&cors, origin).await, None);
+    }
+
+    // Alternations are anchored as a whole
+    let cors = Cors::new()
+        .allow_origin_regex(r"https://a\.com|https://b\.com")
+        .unwrap();
+    assert_eq!(allowed_origin(&cors, "https://a.com.evil.net").await, None);
+    assert_eq!(
+        allowed_origin(&cors, "https://evil.net/https://b.com").await,
+        None
+    );
+}
+
+fn preflight(origin: &str, method: &str, headers: Option<&str>) -> Request {
+    let mut req = request(Method::OPTIONS, origin);
+    let req_headers = req.headers_mut();
+    req_headers.insert(
+        header::ACCESS_CONTROL_REQUEST_METHOD,
+        method.parse().unwrap(),
+    );
+    if let Some(headers) = headers {
+        req_headers.insert(
+            header::ACCESS_CONTROL_REQUEST_HEADERS,
+            headers.parse().unwrap(),
+        );
+    }
+    req
+}
+
+fn vary(headers: &header::HeaderMap) -> Vec<&str> {
+    headers
+        .get_all(header::VARY)
+        .iter()
+        .map(|v| v.to_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn preflight_requests() {
+    let (before, _) = Cors::new()
+        .allow_origin("https://example.com")
+        .allow_methods(vec![Method::GET, Method::PUT])
+        .allow_headers(vec!["content-type", "x-token"])
+        .allow_credentials(true)
+        .max_age(Duration::from_secs(600))
+        .build();
+
+    let mut req = preflight("https://example.com", "PUT", Some("X-Token, content-type"));
+    let res = match before.intercept(&mut req).await.unwrap() {
+        BeforeOutcome::Respond(res) => res,
+        BeforeOutcome::Continue => panic!("preflight request not answered"),
+    };
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let headers = res.headers();
+    assert_eq!(
+        headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://example.com"
+    );
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_METHODS], "GET, PUT");
+    assert_eq!(
+        headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+        "content-type, x-token"
+    );
+    assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    assert_eq!(
+        vary(headers),
+        [
+            "origin",
+            "access-control-request-method",
+            "access-control-request-headers"
+        ]
+    );
+
+    for mut req in [
+        preflight("https://evil.net", "PUT", None),
+        preflight("https://example.com", "DELETE", None),
+        preflight("https://example.com", "GET", Some("x-other")),
+    ] {
+        let err = before.intercept(&mut req).await.unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::FORBIDDEN));
+    }
+
+    // Plain `OPTIONS` requests are not preflight requests
+    let mut req = request(Method::OPTIONS, "https://example.com");
+    assert!(matches!(
+        before.intercept(&mut req).await.unwrap(),
+        BeforeOutcome::Continue
+    ));
+}
+
+#[tokio::test]
+async fn responses_vary_on_origin() {
+    let (_, after) = Cors::new()
+        .allow_origin("https://example.com")
+        .expose_headers(vec!["x-request-id"])
+        .build();
+
+    let mut res = Response::new(Body::empty());
+    res.headers_mut()
+        .insert(header::VARY, "Accept-Encoding".parse().unwrap());
+    let res = after
+        .after(&mut request(Method::GET, "https://example.com"), res)
+        .await
+        .unwrap();
+    assert_eq!(
+        res.headers()[header::ACCESS_CONTROL_EXPOSE_HEADERS],
+        "x-request-id"
+    );
+    assert_eq!(vary(res.headers()), ["Accept-Encoding", "origin"]);
+
+    // Responses to other origins vary as well, without the CORS headers
+    let res = after
+        .after(
+            &mut request(Method::GET, "https://evil.net"),
+            Response::new(Body::empty()),
+        )
+        .await
+        .unwrap();
+    assert!(!res
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    assert_eq!(vary(res.headers()), ["origin"]);
+
+    // `Vary` values are not duplicated
+    let mut res = Response::new(Body::empty());
+    res.headers_mut()
+        .insert(header::VARY, "Origin".parse().unwrap());
+    let res = after
+        .after(&mut request(Method::GET, "https://example.com"), res)
+        .await
+        .unwrap();
+    assert_eq!(vary(res.headers()), ["Origin"]);
+}
+
+#[tokio::test]
+async fn errors_get_the_cors_headers() {
+    let (_, after) = Cors::new().allow_origin("https://example.com").build();
+
+    let err = http_error_not_found!("not found")
+        .with_header(header::VARY, "accept-encoding".parse().unwrap());
+    let err = after
+        .catch(&mut request(Method::GET, "https://example.com"), err)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+    assert_eq!(
+        err.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://example.com"
+    );
+    assert_eq!(vary(err.headers()), ["accept-encoding", "origin"]);
+}
@@ -0,0 +1,129 @@
+use hyper_middleware::{Body, Cidr, ClientInfo, Request, TrustedProxies};
+use std::net::{IpAddr, SocketAddr};
+
+fn proxies() -> TrustedProxies {
+    TrustedProxies::new(vec![
+        "10.0.0.0/8".parse().unwrap(),
+        "fd00::/8".parse().unwrap(),
+    ])
+}
+
+fn request(peer: &str, headers: &[(&str, &str)]) -> Request {
+    let mut builder = hyper::Request::builder()
+        .uri("/")
+        .header("host", "origin.internal");
+    for (name, value) in headers {
+        builder = builder.header(*name, *value);
+    }
+    let mut req = builder.body(Body::empty()).unwrap();
+    req.extensions_mut()
+        .insert(SocketAddr::new(peer.parse().unwrap(), 443));
+    req
+}
+
+fn ip(ip: &str) -> Option<IpAddr> {
+    Some(ip.parse().unwrap())
+}
+
+#[test]
+fn untrusted_peer_headers_are_ignored() {
+    let req = request(
+        "203.0.113.7",
+        &[
+            ("forwarded", "for=198.51.100.1;proto=https"),
+            ("x-forwarded-for", "198.51.100.1"),
+        ],
+    );
+    assert_eq!(
+        proxies().resolve(&req),
+        ClientInfo {
+            ip: ip("203.0.113.7"),
+            scheme: "http".to_owned(),
+            host: Some("origin.internal".to_owned()),
+        }
+    );
+}
+
+#[test]
+fn forwarded_header() {
+    let req = request(
+        "10.0.0.1",
+        &[(
+            "forwarded",
+            "for=198.51.100.1;proto=HTTPS;host=example.com, for=\"[fd00::2]:8080\"",
+        )],
+    );
+    assert_eq!(
+        proxies().resolve(&req),
+        ClientInfo {
+            ip: ip("198.51.100.1"),
+            scheme: "https".to_owned(),
+            host: Some("example.com".to_owned()),
+        }
+    );
+}
+
+#[test]
+fn spoofed_x_forwarded_for_entries_are_skipped() {
+    // The client sent `X-Forwarded-For: 1.2.3.4` itself, the trusted proxy appended its real address
+    let req = request(
+        "10.0.0.1",
+        &[
+            ("x-forwarded-for", "1.2.3.4, 198.51.100.1, 10.0.0.2"),
+            ("x-forwarded-proto", "https"),
+        ],
+    );
+    let info = proxies().resolve(&req);
+    assert_eq!(info.ip, ip("198.51.100.1"));
+    assert_eq!(info.scheme, "https");
+
+    // Multiple header fields are joined in order
+    let req = request(
+        "10.0.0.1",
+        &[
+            ("x-forwarded-for", "1.2.3.4"),
+            ("x-forwarded-for", "198.51.100.1"),
+        ],
+    );
+    assert_eq!(proxies().resolve(&req).ip, ip("198.51.100.1"));
+}
+
+#[test]
+fn all_trusted_hops_resolve_to_the_leftmost_one() {
+    let req = request("10.0.0.1", &[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+    assert_eq!(proxies().resolve(&req).ip, ip("10.0.0.3"));
+}
+
+#[test]
+fn unknown_or_obfuscated_clients_have_no_ip() {
+    for value in [
+        "for=unknown",
+        "for=_hidden, for=10.0.0.2",
+        "for=198.51.100.1, for=unknown;proto=https",
+    ] {
+        let req = request("10.0.0.1", &[("forwarded", value)]);
+        assert_eq!(proxies().resolve(&req).ip, None, "forwarded `{}`", value);
+    }
+
+    let req = request("10.0.0.1", &[("x-forwarded-for", "unknown, 10.0.0.2")]);
+    assert_eq!(proxies().resolve(&req).ip, None);
+}
+
+#[test]
+fn ipv4_mapped_addresses_are_canonicalized() {
+    let req = request(
+        "::ffff:10.0.0.1",
+        &[("x-forwarded-for", "::ffff:198.51.100.1")],
+    );
+    assert_eq!(proxies().resolve(&req).ip, ip("198.51.100.1"));
+}
+
+#[test]
+fn invalid_cidrs_are_plain_errors() {
+    for cidr in ["10.0.0.0/33", "fd00::/129", "10.0.0/8", "10.0.0.0/x", ""] {
+        let err = cidr.parse::<Cidr>().unwrap_err();
+        // Configuration errors are not HTTP errors
+        assert_eq!(err.status(), None, "CIDR `{}`", cidr);
+    }
+    assert!(Cidr::new(ip("10.0.0.0").unwrap(), 40).is_err());
+}
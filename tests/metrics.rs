@@ -0,0 +1,33 @@
+use hyper::Method;
+use hyper_middleware::{Body, Handler, Metrics, Middlewares, Request, Response, Result};
+
+fn request(method: &str) -> Request {
+    hyper::Request::builder()
+        .method(Method::from_bytes(method.as_bytes()).unwrap())
+        .uri("/")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn extension_methods_share_a_label() {
+    let handler = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::empty())) };
+    let metrics = Metrics::new();
+    let mut middlewares = Middlewares::new(handler);
+    middlewares.link(metrics.build());
+
+    for method in ["GET", "PURGE", "X-RANDOM-1", "X-RANDOM-2", "get"] {
+        middlewares.handle(&mut request(method)).await.unwrap();
+    }
+
+    let rendered = metrics.render();
+    assert!(rendered
+        .contains("http_requests_total{method=\"GET\",status=\"2xx\",route=\"unmatched\"} 1\n"));
+    assert!(rendered
+        .contains("http_requests_total{method=\"other\",status=\"2xx\",route=\"unmatched\"} 4\n"));
+    assert!(rendered.contains("http_requests_in_flight{method=\"GET\"} 0\n"));
+    assert!(rendered.contains("http_requests_in_flight{method=\"other\"} 0\n"));
+    for method in ["PURGE", "X-RANDOM", "get"] {
+        assert!(!rendered.contains(method), "{}", rendered);
+    }
+}
@@ -0,0 +1,192 @@
+use hyper_middleware::{ProxyHeader, ProxyProtocol};
+use std::io;
+use tokio::io::AsyncReadExt;
+
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+// Build a version 2 header with the given command, family and address block.
+fn v2(command: u8, family: u8, addrs: &[u8]) -> Vec<u8> {
+    let mut buf = V2_SIGNATURE.to_vec();
+    buf.push(0x20 | command);
+    buf.push(family);
+    buf.extend_from_slice(&(addrs.len() as u16).to_be_bytes());
+    buf.extend_from_slice(addrs);
+    buf
+}
+
+fn tcp4_addrs() -> Vec<u8> {
+    let mut addrs = vec![192, 0, 2, 1, 10, 0, 0, 1];
+    addrs.extend_from_slice(&4000u16.to_be_bytes());
+    addrs.extend_from_slice(&443u16.to_be_bytes());
+    addrs
+}
+
+async fn accept(config: &ProxyProtocol, bytes: &[u8]) -> io::Result<(Option<ProxyHeader>, String)> {
+    let mut stream = config.accept(bytes).await?;
+    let header = stream.header().copied();
+    let mut rest = String::new();
+    stream.read_to_string(&mut rest).await?;
+    Ok((header, rest))
+}
+
+#[tokio::test]
+async fn v1_headers() {
+    let config = ProxyProtocol::new();
+
+    let (header, rest) = accept(&config, b"PROXY TCP4 192.0.2.1 10.0.0.1 4000 443\r\nGET /")
+        .await
+        .unwrap();
+    assert_eq!(
+        header,
+        Some(ProxyHeader {
+            version: 1,
+            source: Some("192.0.2.1:4000".parse().unwrap()),
+            destination: Some("10.0.0.1:443".parse().unwrap()),
+        })
+    );
+    assert_eq!(rest, "GET /");
+
+    let (header, _) = accept(&config, b"PROXY TCP6 2001:db8::1 ::1 4000 443\r\n")
+        .await
+        .unwrap();
+    assert_eq!(
+        header.unwrap().source,
+        Some("[2001:db8::1]:4000".parse().unwrap())
+    );
+
+    let (header, _) = accept(&config, b"PROXY UNKNOWN\r\n").await.unwrap();
+    assert_eq!(header.unwrap().source, None);
+}
+
+#[tokio::test]
+async fn invalid_v1_headers() {
+    let config = ProxyProtocol::new();
+
+    for bytes in [
+        &b"PROXY TCP4 2001:db8::1 10.0.0.1 4000 443\r\n"[..],
+        b"PROXY TCP4 192.0.2.1 10.0.0.1 99999 443\r\n",
+        b"PROXY TCP4 192.0.2.1 10.0.0.1 4000\r\n",
+        b"PROXY UDP4 192.0.2.1 10.0.0.1 4000 443\r\n",
+    ] {
+        let err = accept(&config, bytes).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    // Longer than 107 bytes
+    let mut long = b"PROXY UNKNOWN ".to_vec();
+    long.extend(std::iter::repeat(b'a').take(100));
+    long.extend_from_slice(b"\r\n");
+    let err = accept(&config, &long).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn v2_headers() {
+    let config = ProxyProtocol::new();
+
+    let mut bytes = v2(0x1, 0x11, &tcp4_addrs());
+    bytes.extend_from_slice(b"GET /");
+    let (header, rest) = accept(&config, &bytes).await.unwrap();
+    assert_eq!(
+        header,
+        Some(ProxyHeader {
+            version: 2,
+            source: Some("192.0.2.1:4000".parse().unwrap()),
+            destination: Some("10.0.0.1:443".parse().unwrap()),
+        })
+    );
+    assert_eq!(rest, "GET /");
+
+    let mut addrs = [0u8; 36];
+    addrs[15] = 1;
+    addrs[31] = 1;
+    addrs[32..34].copy_from_slice(&4000u16.to_be_bytes());
+    let (header, _) = accept(&config, &v2(0x1, 0x21, &addrs)).await.unwrap();
+    assert_eq!(header.unwrap().source, Some("[::1]:4000".parse().unwrap()));
+
+    // LOCAL connections carry no addresses
+    let (header, _) = accept(&config, &v2(0x0, 0x00, &[])).await.unwrap();
+    assert_eq!(header.unwrap().source, None);
+}
+
+#[tokio::test]
+async fn oversized_v2_address_blocks_skip_the_tlvs() {
+    let mut addrs = tcp4_addrs();
+    // A `PP2_TYPE_AUTHORITY` TLV
+    addrs.extend_from_slice(&[0x02, 0x00, 0x04]);
+    addrs.extend_from_slice(b"host");
+    let mut bytes = v2(0x1, 0x11, &addrs);
+    bytes.extend_from_slice(b"GET /");
+
+    let (header, rest) = accept(&ProxyProtocol::new(), &bytes).await.unwrap();
+    assert_eq!(
+        header.unwrap().source,
+        Some("192.0.2.1:4000".parse().unwrap())
+    );
+    assert_eq!(rest, "GET /");
+}
+
+#[tokio::test]
+async fn invalid_v2_headers() {
+    let config = ProxyProtocol::new();
+
+    // Address blocks shorter than the family addresses
+    for (family, len) in [(0x11, 11), (0x21, 35)] {
+        let err = accept(&config, &v2(0x1, family, &vec![0; len]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    // Unknown address family
+    let err = accept(&config, &v2(0x1, 0x41, &tcp4_addrs()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // Unknown command
+    let err = accept(&config, &v2(0x2, 0x11, &tcp4_addrs()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // Unsupported version
+    let mut bytes = v2(0x1, 0x11, &tcp4_addrs());
+    bytes[12] = 0x11;
+    let err = accept(&config, &bytes).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // Unspecified and Unix socket families carry no IP addresses
+    for family in [0x00, 0x31] {
+        let (header, _) = accept(&config, &v2(0x1, family, &[0; 216])).await.unwrap();
+        assert_eq!(header.unwrap().source, None);
+    }
+}
+
+#[tokio::test]
+async fn truncated_headers() {
+    let config = ProxyProtocol::new();
+
+    let bytes = v2(0x1, 0x11, &tcp4_addrs());
+    for len in [5, 14, bytes.len() - 1] {
+        let err = accept(&config, &bytes[..len]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "length {}", len);
+    }
+
+    let err = accept(&config, b"PROXY TCP4 192.0.2.1").await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[tokio::test]
+async fn missing_headers() {
+    let (header, rest) = accept(&ProxyProtocol::new(), b"GET / HTTP/1.1\r\n")
+        .await
+        .unwrap();
+    assert_eq!(header, None);
+    assert_eq!(rest, "GET / HTTP/1.1\r\n");
+
+    let err = accept(&ProxyProtocol::new().strict(true), b"GET / HTTP/1.1\r\n")
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,249 @@
+use hyper::body::to_bytes;
+use hyper::{header, Method, StatusCode};
+use hyper_middleware::{
+    http_error_unauthorized, Body, Handler, MatchedPath, Middlewares, OriginalUri, Params, Request,
+    Response, Result, Router,
+};
+
+// Respond with the matched path and the captured parameters.
+fn echo(name: &'static str) -> impl Fn(&mut Request) -> Result<Response> {
+    move |req: &mut Request| {
+        let matched = req.extensions().get::<MatchedPath>().unwrap();
+        let params = req
+            .extensions()
+            .get::<Params>()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+        Ok(Response::new(Body::from(format!(
+            "{} {} {}",
+            name,
+            matched.as_str(),
+            params
+        ))))
+    }
+}
+
+fn request(method: Method, uri: &str) -> Request {
+    hyper::Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap()
+}
+
+async fn get(router: &Router, uri: &str) -> String {
+    call(router, Method::GET, uri).await.unwrap()
+}
+
+async fn call(router: &Router, method: Method, uri: &str) -> Result<String> {
+    let res = router.handle(&mut request(method, uri)).await?;
+    let body = to_bytes(res.into_body()).await.unwrap();
+    Ok(String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn params_and_wildcards() {
+    let mut router = Router::new();
+    router
+        .get("/users/:id", echo("user"))
+        .get("/users/:id/posts/:post", echo("post"))
+        .get("/static/*rest", echo("static"));
+
+    assert_eq!(get(&router, "/users/42").await, "user /users/:id id=42");
+    assert_eq!(
+        get(&router, "/users/42/posts/7?x=1").await,
+        "post /users/:id/posts/:post id=42&post=7"
+    );
+    assert_eq!(
+        get(&router, "/static/css/site.css").await,
+        "static /static/*rest rest=css/site.css"
+    );
+    // Wildcards match zero segments as well
+    assert_eq!(get(&router, "/static").await, "static /static/*rest rest=");
+    // Empty segments are ignored
+    assert_eq!(get(&router, "//users//42/").await, "user /users/:id id=42");
+}
+
+#[tokio::test]
+async fn params_are_percent_decoded() {
+    let mut router = Router::new();
+    router
+        .get("/users/:id", echo("user"))
+        .get("/files/*path", echo("files"));
+
+    assert_eq!(
+        get(&router, "/users/J%C3%B6rg%20M").await,
+        "user /users/:id id=Jörg M"
+    );
+    assert_eq!(
+        get(&router, "/files/a%2Fb/c%41").await,
+        "files /files/*path path=a/b/cA"
+    );
+    // Invalid escapes and invalid UTF-8 are kept as is
+    assert_eq!(get(&router, "/users/%+f").await, "user /users/:id id=%+f");
+    assert_eq!(get(&router, "/users/%4").await, "user /users/:id id=%4");
+    assert_eq!(get(&router, "/users/%zz1").await, "user /users/:id id=%zz1");
+    assert_eq!(get(&router, "/users/%FF").await, "user /users/:id id=%FF");
+}
+
+#[tokio::test]
+async fn most_specific_route_wins() {
+    let mut router = Router::new();
+    router
+        .get("/*rest", echo("wildcard"))
+        .get("/users/:id", echo("param"))
+        .get("/users/me", echo("static"));
+
+    assert_eq!(get(&router, "/users/me").await, "static /users/me ");
+    assert_eq!(get(&router, "/users/1").await, "param /users/:id id=1");
+    assert_eq!(get(&router, "/other").await, "wildcard /*rest rest=other");
+}
+
+#[tokio::test]
+async fn head_falls_back_to_get() {
+    let mut router = Router::new();
+    router
+        .get("/a", echo("get a"))
+        .get("/b", echo("get b"))
+        .head("/b", echo("head b"));
+
+    assert_eq!(
+        call(&router, Method::HEAD, "/a").await.unwrap(),
+        "get a /a "
+    );
+    assert_eq!(
+        call(&router, Method::HEAD, "/b").await.unwrap(),
+        "head b /b "
+    );
+}
+
+#[tokio::test]
+async fn not_found_and_method_not_allowed() {
+    let mut router = Router::new();
+    router
+        .get("/items/:id", echo("get"))
+        .delete("/items/:id", echo("delete"))
+        .post("/items/*rest", echo("post"));
+
+    let err = call(&router, Method::GET, "/other").await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+
+    // The methods of every route matching the path are allowed
+    let err = call(&router, Method::PUT, "/items/1").await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::METHOD_NOT_ALLOWED));
+    assert_eq!(err.headers()[header::ALLOW], "DELETE, GET, HEAD, POST");
+
+    // A less specific route handles the method
+    assert_eq!(
+        call(&router, Method::POST, "/items/1").await.unwrap(),
+        "post /items/*rest rest=1"
+    );
+}
+
+#[tokio::test]
+async fn routes_take_precedence_over_mounts() {
+    let mut api = Router::new();
+    api.get("/*rest", echo("mounted"));
+
+    let mut router = Router::new();
+    router.get("/api/health", echo("route")).mount("/api", api);
+
+    assert_eq!(get(&router, "/api/health").await, "route /api/health ");
+    assert_eq!(
+        get(&router, "/api/users").await,
+        "mounted /api/*rest rest=users"
+    );
+    // A route whose method doesn't match falls through to the mount
+    assert_eq!(
+        call(&router, Method::POST, "/api/health")
+            .await
+            .unwrap_err()
+            .status(),
+        Some(StatusCode::METHOD_NOT_ALLOWED)
+    );
+}
+
+#[tokio::test]
+async fn mounts_strip_the_prefix_and_keep_the_original_uri() {
+    let handler = |req: &mut Request| -> Result<Response> {
+        let original = req.extensions().get::<OriginalUri>().unwrap();
+        let params = req.extensions().get::<Params>().unwrap();
+        Ok(Response::new(Body::from(format!(
+            "{} {} {}",
+            req.uri(),
+            original.0,
+            params.get("tenant").unwrap_or_default()
+        ))))
+    };
+    let mut files = Router::new();
+    files.get("/*path", handler);
+    let mut tenants = Router::new();
+    tenants.mount("/files", files);
+    let mut router = Router::new();
+    router.mount("/tenants/:tenant", tenants);
+
+    // Nested mounts strip their own prefix and keep the outermost original URI
+    assert_eq!(
+        get(&router, "/tenants/acme/files/a/b.txt?v=1").await,
+        "/a/b.txt?v=1 /tenants/acme/files/a/b.txt?v=1 acme"
+    );
+    assert_eq!(
+        get(&router, "/tenants/acme/files").await,
+        "/ /tenants/acme/files acme"
+    );
+
+    // The prefix must match whole segments
+    let err = call(&router, Method::GET, "/tenants/acme/filesystem")
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+}
+
+#[tokio::test]
+async fn mounted_middlewares_only_run_under_their_prefix() {
+    let authorize = |req: &mut Request| -> Result {
+        match req.headers().get(header::AUTHORIZATION) {
+            Some(_) => Ok(()),
+            None => Err(http_error_unauthorized!("authorization required")),
+        }
+    };
+    let tag = |_: &mut Request, mut res: Response| -> Result<Response> {
+        res.headers_mut()
+            .insert("x-admin", header::HeaderValue::from_static("1"));
+        Ok(res)
+    };
+
+    let mut admin = Router::new();
+    admin.get("/dashboard", echo("dashboard"));
+    let mut admin = Middlewares::new(admin);
+    admin.link_before(authorize);
+    admin.link_after(tag);
+
+    let mut router = Router::new();
+    router.get("/public", echo("public")).mount("/admin", admin);
+
+    let res = router
+        .handle(&mut request(Method::GET, "/public"))
+        .await
+        .unwrap();
+    assert!(res.headers().get("x-admin").is_none());
+
+    let err = router
+        .handle(&mut request(Method::GET, "/admin/dashboard"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::UNAUTHORIZED));
+
+    let mut req = request(Method::GET, "/admin/dashboard");
+    req.headers_mut()
+        .insert(header::AUTHORIZATION, "Bearer x".parse().unwrap());
+    let res = router.handle(&mut req).await.unwrap();
+    assert_eq!(res.headers()["x-admin"], "1");
+    let body = to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "dashboard /admin/dashboard ");
+    // The URI is restored once the mounted handler returns
+    assert_eq!(req.uri(), "/admin/dashboard");
+}
@@ -0,0 +1,179 @@
+use hyper::body::to_bytes;
+use hyper::{header, StatusCode};
+use hyper_middleware::{Body, Handler, Request, StaticFiles};
+use std::path::PathBuf;
+
+const CONTENT: &str = "0123456789abcdefghij";
+
+// Create an empty temporary directory unique to the given test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("hyper-middleware-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn request(path: &str, range: Option<&str>) -> Request {
+    let mut builder = hyper::Request::builder().uri(path);
+    if let Some(range) = range {
+        builder = builder.header(header::RANGE, range);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+async fn get_range(handler: &StaticFiles, range: &str) -> (StatusCode, hyper::HeaderMap, String) {
+    match handler.handle(&mut request("/file.txt", Some(range))).await {
+        Ok(res) => {
+            let (parts, body) = res.into_parts();
+            let body = to_bytes(body).await.unwrap();
+            (
+                parts.status,
+                parts.headers,
+                String::from_utf8_lossy(&body).into_owned(),
+            )
+        }
+        Err(err) => {
+            let status = err.status().unwrap();
+            (status, err.headers().clone(), String::new())
+        }
+    }
+}
+
+fn files(name: &str) -> StaticFiles {
+    let root = temp_dir(name);
+    std::fs::write(root.join("file.txt"), CONTENT).unwrap();
+    StaticFiles::new(root)
+}
+
+#[tokio::test]
+async fn single_ranges() {
+    let handler = files("single-ranges");
+
+    let (status, headers, body) = get_range(&handler, "bytes=2-5").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers[header::CONTENT_RANGE], "bytes 2-5/20");
+    assert_eq!(body, "2345");
+
+    let (status, headers, body) = get_range(&handler, "bytes=-3").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers[header::CONTENT_RANGE], "bytes 17-19/20");
+    assert_eq!(body, "hij");
+
+    let (status, headers, body) = get_range(&handler, "bytes=15-").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers[header::CONTENT_RANGE], "bytes 15-19/20");
+    assert_eq!(body, "fghij");
+
+    // The end is clamped to the file length
+    let (status, headers, _) = get_range(&handler, "bytes=10-999").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers[header::CONTENT_RANGE], "bytes 10-19/20");
+}
+
+#[tokio::test]
+async fn multiple_ranges() {
+    let handler = files("multiple-ranges");
+
+    let (status, headers, body) = get_range(&handler, "bytes=0-1, 18-").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    let content_type = headers[header::CONTENT_TYPE].to_str().unwrap();
+    assert!(content_type.starts_with("multipart/byteranges; boundary="));
+    assert_eq!(headers[header::CONTENT_LENGTH], body.len().to_string());
+    assert!(body.contains("Content-Range: bytes 0-1/20\r\n\r\n01\r\n"));
+    assert!(body.contains("Content-Range: bytes 18-19/20\r\n\r\nij\r\n"));
+
+    // Unsatisfiable ranges of a set are skipped
+    let (status, headers, body) = get_range(&handler, "bytes=50-60, 3-4").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers[header::CONTENT_RANGE], "bytes 3-4/20");
+    assert_eq!(body, "34");
+}
+
+#[tokio::test]
+async fn malformed_ranges_are_ignored() {
+    let handler = files("malformed-ranges-are-ignored");
+
+    for range in [
+        "bytes=5-2",
+        "bytes=a-b",
+        "bytes=1-2-3",
+        "bytes=--1",
+        "items=0-1",
+        "bytes 0-1",
+        "bytes=0-1,x",
+    ] {
+        let (status, headers, body) = get_range(&handler, range).await;
+        assert_eq!(status, StatusCode::OK, "range `{}`", range);
+        assert!(headers.get(header::CONTENT_RANGE).is_none());
+        assert_eq!(body, CONTENT);
+    }
+
+    // Too many ranges
+    let many = (0..40)
+        .map(|i| format!("{}-{}", i % 20, i % 20))
+        .collect::<Vec<_>>()
+        .join(",");
+    let (status, _, body) = get_range(&handler, &format!("bytes={}", many)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, CONTENT);
+}
+
+#[tokio::test]
+async fn unsatisfiable_ranges() {
+    let handler = files("unsatisfiable-ranges");
+
+    for range in ["bytes=20-", "bytes=-0", "bytes=30-40, 50-"] {
+        let (status, headers, _) = get_range(&handler, range).await;
+        assert_eq!(
+            status,
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            "range `{}`",
+            range
+        );
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes */20");
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlinked_index_outside_of_root_is_not_served() {
+    let dir = temp_dir("index-symlink");
+    let root = dir.join("root");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+    std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("index.html")).unwrap();
+
+    let handler = StaticFiles::new(&root);
+    let err = handler.handle(&mut request("/", None)).await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlinked_sidecar_outside_of_root_is_not_served() {
+    let dir = temp_dir("sidecar-symlink");
+    let root = dir.join("root");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("file.txt"), CONTENT).unwrap();
+    std::fs::write(dir.join("secret.gz"), "secret").unwrap();
+    std::os::unix::fs::symlink(dir.join("secret.gz"), root.join("file.txt.gz")).unwrap();
+
+    let handler = StaticFiles::new(&root).precompressed(true);
+    let mut req = request("/file.txt", None);
+    req.headers_mut()
+        .insert(header::ACCEPT_ENCODING, "gzip".parse().unwrap());
+    let res = handler.handle(&mut req).await.unwrap();
+    assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+    assert_eq!(to_bytes(res.into_body()).await.unwrap(), CONTENT);
+}
+
+#[tokio::test]
+async fn paths_below_a_file_are_not_found() {
+    let handler = files("below-file");
+
+    for path in ["/file.txt/x", "/file.txt/x/y"] {
+        let err = handler.handle(&mut request(path, None)).await.unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::NOT_FOUND), "path `{}`", path);
+    }
+}
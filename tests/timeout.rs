@@ -0,0 +1,126 @@
+use hyper::{Method, StatusCode};
+use hyper_middleware::{
+    async_trait, AroundMiddleware, Body, Handler, Request, Response, Result, Timeout, Timer,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// A timer which expires right away and records the requested durations.
+#[derive(Clone, Default)]
+struct Expired(Arc<Mutex<Vec<Duration>>>);
+
+impl Timer for Expired {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        self.0.lock().unwrap().push(duration);
+        Box::pin(async {})
+    }
+}
+
+// A timer which never expires.
+struct Never;
+
+impl Timer for Never {
+    fn sleep(&self, _: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(std::future::pending())
+    }
+}
+
+// A handler which never responds.
+struct Stalled;
+
+#[async_trait]
+impl Handler for Stalled {
+    async fn handle(&self, _: &mut Request) -> Result<Response> {
+        std::future::pending().await
+    }
+
+    fn name(&self) -> &'static str {
+        "stalled"
+    }
+}
+
+fn request(method: Method, uri: &str) -> Request {
+    hyper::Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+}
+
+#[tokio::test]
+async fn most_specific_rule_wins() {
+    let timer = Expired::default();
+    let timeout = Timeout::new(secs(1))
+        .route("/api", secs(2))
+        .route("/api/uploads/", secs(3))
+        .method(Method::POST, secs(4))
+        .route_method("/api", Method::POST, secs(5))
+        .with_timer(timer.clone());
+    let handler = timeout.around(Box::new(Stalled)).await;
+
+    for (method, uri) in [
+        (Method::GET, "/"),
+        (Method::GET, "/apis"),
+        (Method::GET, "/api"),
+        (Method::GET, "/api/users"),
+        (Method::GET, "/api/uploads/a"),
+        (Method::POST, "/"),
+        (Method::POST, "/api/users"),
+        // The longest prefix takes precedence over the method
+        (Method::POST, "/api/uploads"),
+    ] {
+        handler.handle(&mut request(method, uri)).await.unwrap_err();
+    }
+
+    assert_eq!(*timer.0.lock().unwrap(), [1, 1, 2, 2, 3, 4, 5, 3].map(secs));
+}
+
+#[tokio::test]
+async fn expired_requests_fail() {
+    let handler = Timeout::new(secs(1))
+        .with_timer(Expired::default())
+        .around(Box::new(Stalled))
+        .await;
+    let err = handler
+        .handle(&mut request(Method::GET, "/"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::GATEWAY_TIMEOUT));
+
+    let handler = Timeout::new(secs(1))
+        .with_timer(Expired::default())
+        .with_service_unavailable()
+        .around(Box::new(Stalled))
+        .await;
+    let err = handler
+        .handle(&mut request(Method::GET, "/"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+}
+
+#[tokio::test]
+async fn responses_in_time_pass_through() {
+    let ok = |_: &mut Request| -> Result<Response> { Ok(Response::new(Body::from("ok"))) };
+    let handler = Timeout::new(secs(1))
+        .with_timer(Never)
+        .around(Box::new(ok))
+        .await;
+    let res = handler
+        .handle(&mut request(Method::GET, "/"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn handler_name_is_forwarded() {
+    let handler = Timeout::new(secs(1)).around(Box::new(Stalled)).await;
+    assert_eq!(handler.name(), "stalled");
+}
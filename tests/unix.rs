@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+use futures_util::future::poll_fn;
+use hyper::server::accept::Accept;
+use hyper_middleware::UnixIncoming;
+use std::fs::File;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+fn set_open_files_limit(limit: libc::rlim_t) -> libc::rlim_t {
+    let mut rlimit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    unsafe {
+        assert_eq!(libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlimit), 0);
+        let previous = rlimit.rlim_cur;
+        rlimit.rlim_cur = limit;
+        assert_eq!(libc::setrlimit(libc::RLIMIT_NOFILE, &rlimit), 0);
+        previous
+    }
+}
+
+#[tokio::test]
+async fn running_out_of_file_descriptors_does_not_stop_the_server() {
+    let path = std::env::temp_dir().join(format!(
+        "hyper-middleware-emfile-{}.sock",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let mut incoming = UnixIncoming::bind(&path).unwrap();
+
+    // Use up every file descriptor but the one of the client
+    let previous = set_open_files_limit(256);
+    let mut files = vec![];
+    while let Ok(file) = File::open("/dev/null") {
+        files.push(file);
+    }
+    files.pop();
+    let _client = std::os::unix::net::UnixStream::connect(&path).unwrap();
+
+    let mut accept = poll_fn(|cx| Pin::new(&mut incoming).poll_accept(cx));
+    let accepted = tokio::time::timeout(Duration::from_millis(200), &mut accept).await;
+    assert!(accepted.is_err(), "the listener should wait for resources");
+
+    let start = Instant::now();
+    files.clear();
+    set_open_files_limit(previous);
+    let stream = accept.await.unwrap().unwrap();
+    assert!(stream.peer_cred().is_ok());
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    let _ = std::fs::remove_file(&path);
+}